@@ -16,6 +16,7 @@ use std::fmt;
 use std::error::Error;
 
 use bitreader::{BitReader,BitReaderError};
+use bitwriter::{BitWriter,BitWriterError};
 
 pub type DeserializationResult<T> = Result<T, DeserializationError>;
 
@@ -32,6 +33,10 @@ pub enum DeserializationError {
         position: u64,
         max_position: u64,
     },
+    CrcMismatch {
+        expected: u32,
+        computed: u32,
+    },
 }
 
 impl Error for DeserializationError {
@@ -40,6 +45,7 @@ impl Error for DeserializationError {
             DeserializationError::UnexpectedValue{..} => "Invalid value in the source data",
             DeserializationError::BitReaderError(ref err) => err.description(),
             DeserializationError::ReadTooMuch{..} => "Read more data than allowed",
+            DeserializationError::CrcMismatch{..} => "Section CRC does not match its contents",
         }
     }
 }
@@ -52,6 +58,8 @@ impl fmt::Display for DeserializationError {
             DeserializationError::BitReaderError(ref err) => err.fmt(fmt),
             DeserializationError::ReadTooMuch{ position, max_position } =>
                 write!(fmt, "Read to position {}, when maximum allowed position was {}", position, max_position),
+            DeserializationError::CrcMismatch{ expected, computed } =>
+                write!(fmt, "Section CRC was {:08x}, but calculated CRC was {:08x}", expected, computed),
         }
     }
 }
@@ -62,13 +70,198 @@ impl From<BitReaderError> for DeserializationError {
     }
 }
 
+/// Options that change how strictly data is checked while deserializing.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct DeserializeOptions {
+    /// Check the CRC_32 of long form sections. Disable only for streams that are known to carry
+    /// bad CRCs.
+    pub verify_crc: bool,
+}
+
+impl DeserializeOptions {
+    pub fn new() -> DeserializeOptions {
+        DeserializeOptions {
+            verify_crc: true,
+        }
+    }
+}
+
+impl Default for DeserializeOptions {
+    fn default() -> DeserializeOptions {
+        DeserializeOptions::new()
+    }
+}
+
 pub trait Deserialize: Sized {
     fn deserialize(reader: &mut BitReader) -> DeserializationResult<Self>;
 
+    /// Deserialize with non-default options. Types that are not affected by the options need not
+    /// implement this.
+    fn deserialize_with_options(reader: &mut BitReader, options: &DeserializeOptions) -> DeserializationResult<Self> {
+        let _ = options;
+        Self::deserialize(reader)
+    }
+
     fn from_bytes(bytes: &[u8]) -> DeserializationResult<Self> {
         let mut reader = BitReader::new(bytes);
         Deserialize::deserialize(&mut reader)
     }
+
+    fn from_bytes_with_options(bytes: &[u8], options: &DeserializeOptions) -> DeserializationResult<Self> {
+        let mut reader = BitReader::new(bytes);
+        Deserialize::deserialize_with_options(&mut reader, options)
+    }
+}
+
+pub type SerializationResult<T> = Result<T, SerializationError>;
+
+#[derive(Debug,Clone,Copy)]
+pub enum SerializationError {
+    BitWriterError(BitWriterError),
+    /// The data written does not end at a byte boundary
+    NotByteAligned {
+        position: u64,
+    },
+    /// A value that can not be represented in the serialized form
+    InvalidValue(&'static str),
+    /// The section_length exceeds the limit for the table
+    SectionTooLong {
+        length: usize,
+        max_length: usize,
+    },
+}
+
+impl Error for SerializationError {
+    fn description(&self) -> &str {
+        match *self {
+            SerializationError::BitWriterError(ref err) => err.description(),
+            SerializationError::NotByteAligned{..} => "Serialized data does not end at a byte boundary",
+            SerializationError::InvalidValue(..) => "Value can not be serialized",
+            SerializationError::SectionTooLong{..} => "Section is too long",
+        }
+    }
+}
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SerializationError::BitWriterError(ref err) => err.fmt(fmt),
+            SerializationError::NotByteAligned{ position } =>
+                write!(fmt, "Serialized data ends at bit position {}, which is not a byte boundary", position),
+            SerializationError::InvalidValue(reason) => write!(fmt, "Value can not be serialized: {}", reason),
+            SerializationError::SectionTooLong{ length, max_length } =>
+                write!(fmt, "Section length {} exceeds the maximum of {}", length, max_length),
+        }
+    }
+}
+
+impl From<BitWriterError> for SerializationError {
+    fn from(err: BitWriterError) -> SerializationError {
+        SerializationError::BitWriterError(err)
+    }
+}
+
+/// The counterpart of `Deserialize`: deserializing the serialized form of a value gives back an
+/// equal value.
+///
+/// Serializing a deserialized value does not always give back the original bytes, as data that is
+/// skipped when deserializing is not kept: stuffing, unknown bytes at the end of structures and
+/// the undecoded contents of `IodDescriptor`, `MuxCodeDescriptor` and `FmxBufferSizeDescriptor`.
+pub trait Serialize {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()>;
+
+    fn to_bytes(&self) -> SerializationResult<Vec<u8>> {
+        let mut writer = BitWriter::new();
+        try!(self.serialize(&mut writer));
+        if !writer.is_aligned() {
+            return Err(SerializationError::NotByteAligned {
+                position: writer.position(),
+            });
+        }
+        Ok(writer.into_bytes())
+    }
+}
+
+impl<'a, T: Serialize + ?Sized> Serialize for &'a T {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()> {
+        (**self).serialize(writer)
+    }
+}
+
+impl Serialize for () {
+    fn serialize(&self, _: &mut BitWriter) -> SerializationResult<()> {
+        Ok(())
+    }
+}
+
+impl Serialize for u8 {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()> {
+        Ok(try!(writer.write_u8(8, *self)))
+    }
+}
+
+impl Serialize for u16 {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()> {
+        Ok(try!(writer.write_u16(16, *self)))
+    }
+}
+
+impl Serialize for u32 {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()> {
+        Ok(try!(writer.write_u32(32, *self)))
+    }
+}
+
+impl<T: Serialize> Serialize for Vec<T> {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()> {
+        for item in self {
+            try!(item.serialize(writer));
+        }
+        Ok(())
+    }
+}
+
+impl<T: Serialize> Serialize for Option<T> {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()> {
+        match *self {
+            Some(ref value) => value.serialize(writer),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Number of whole bytes in the serialized form of a value, for filling in length fields.
+pub fn serialized_length<T: Serialize + ?Sized>(value: &T) -> SerializationResult<u64> {
+    let mut writer = BitWriter::new();
+    try!(value.serialize(&mut writer));
+    Ok(writer.position() / 8)
+}
+
+pub fn write_reserved(writer: &mut BitWriter, bits: u8) -> SerializationResult<()> {
+    let all_on: u64 = !0;
+    Ok(try!(writer.write_u64(bits, all_on >> (64 - bits))))
+}
+
+/// Data preceded by its 8-bit length.
+pub fn length_prefixed(data: &[u8]) -> SerializationResult<Vec<u8>> {
+    if data.len() > 0xff {
+        return Err(SerializationError::InvalidValue("data is longer than 255 bytes"));
+    }
+    let mut bytes = Vec::with_capacity(data.len() + 1);
+    bytes.push(data.len() as u8);
+    bytes.extend(data.iter().cloned());
+    Ok(bytes)
+}
+
+/// Writes the CRC_32 of everything written so far.
+pub fn write_crc(writer: &mut BitWriter) -> SerializationResult<()> {
+    if !writer.is_aligned() {
+        return Err(SerializationError::NotByteAligned {
+            position: writer.position(),
+        });
+    }
+    let crc = ::crc::crc32_mpeg2(writer.bytes());
+    Ok(try!(writer.write_u32(32, crc)))
 }
 
 impl Deserialize for u8 {
@@ -79,13 +272,13 @@ impl Deserialize for u8 {
 
 impl Deserialize for u16 {
     fn deserialize(reader: &mut BitReader) -> DeserializationResult<u16> {
-        Ok(try!(reader.read_u16(8)))
+        Ok(try!(reader.read_u16(16)))
     }
 }
 
 impl Deserialize for u32 {
     fn deserialize(reader: &mut BitReader) -> DeserializationResult<u32> {
-        Ok(try!(reader.read_u32(8)))
+        Ok(try!(reader.read_u32(32)))
     }
 }
 
@@ -129,3 +322,53 @@ pub fn expect(reader: &mut BitReader, bits: u8, reference_value: u64) -> Deseria
 pub fn bool_flag(value: u8) -> bool {
     value == 1
 }
+
+/// Reads the CRC_32 field and, unless disabled in `options`, checks it against the data between
+/// the start of the section (where `section_reader` points to) and the CRC field.
+pub fn verify_crc(mut section_reader: BitReader, reader: &mut BitReader, options: &DeserializeOptions) -> DeserializationResult<()> {
+    let crc_position = reader.position();
+    let expected = try!(reader.read_u32(32));
+    if !options.verify_crc {
+        return Ok(());
+    }
+    let mut bytes = Vec::with_capacity(crc_position as usize / 8);
+    for _ in 0..crc_position / 8 {
+        bytes.push(try!(section_reader.read_u8(8)));
+    }
+    let computed = ::crc::crc32_mpeg2(&bytes[..]);
+    if computed != expected {
+        return Err(DeserializationError::CrcMismatch {
+            expected: expected,
+            computed: computed,
+        });
+    }
+    Ok(())
+}
+
+/// Decodes a binary-coded decimal value of the given number of digits, or None if some of the
+/// digits are not decimal.
+pub fn decode_bcd(value: u64, digits: u8) -> Option<u64> {
+    let mut result = 0;
+    for digit in (0..digits).rev() {
+        let nibble = (value >> (digit as u64 * 4)) & 0xf;
+        if nibble > 9 {
+            return None;
+        }
+        result = result * 10 + nibble;
+    }
+    Some(result)
+}
+
+/// Encodes a value as binary-coded decimal of the given number of digits, or None if the value
+/// does not fit.
+pub fn encode_bcd(mut value: u64, digits: u8) -> Option<u64> {
+    let mut result = 0;
+    for digit in 0..digits {
+        result |= (value % 10) << (digit as u64 * 4);
+        value /= 10;
+    }
+    if value != 0 {
+        return None;
+    }
+    Some(result)
+}
@@ -0,0 +1,248 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Writing big-endian bit fields
+//!
+//! The writing counterpart of `bitreader::BitReader`.
+
+use std::fmt;
+use std::error::Error;
+
+pub type Result<T> = ::std::result::Result<T, BitWriterError>;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum BitWriterError {
+    /// The value does not fit in the requested number of bits
+    ValueTooLarge {
+        position: u64,
+        length: u8,
+        value: u64,
+    },
+    TooManyBitsForType {
+        position: u64,
+        requested: u8,
+        allowed: u8,
+    },
+    /// A length field was not large enough for the length of the data following it
+    LengthTooLarge {
+        position: u64,
+        length: u8,
+        value: u64,
+    },
+}
+
+impl Error for BitWriterError {
+    fn description(&self) -> &str {
+        match *self {
+            BitWriterError::ValueTooLarge{..} => "Value does not fit in the field",
+            BitWriterError::TooManyBitsForType{..} => "Requested more bits than the type has",
+            BitWriterError::LengthTooLarge{..} => "Data is too long for its length field",
+        }
+    }
+}
+
+impl fmt::Display for BitWriterError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BitWriterError::ValueTooLarge{ position, length, value } =>
+                write!(fmt, "Value {} does not fit in {} bits at position {}", value, length, position),
+            BitWriterError::TooManyBitsForType{ position, requested, allowed } =>
+                write!(fmt, "Requested {} bits at position {}, but only {} are allowed", requested, position, allowed),
+            BitWriterError::LengthTooLarge{ position, length, value } =>
+                write!(fmt, "Length {} does not fit in the {} bit length field at position {}", value, length, position),
+        }
+    }
+}
+
+/// Writes bit fields to a growing buffer, most significant bit first.
+#[derive(Debug,Clone)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    position: u64,
+    // Length fields waiting for the data following them: (position, length)
+    open_lengths: Vec<(u64, u8)>,
+}
+
+impl BitWriter {
+    pub fn new() -> BitWriter {
+        BitWriter {
+            bytes: vec![],
+            position: 0,
+            open_lengths: vec![],
+        }
+    }
+
+    pub fn write_u8(&mut self, bit_count: u8, value: u8) -> Result<()> {
+        try!(self.check_bit_count(bit_count, 8));
+        self.write_u64(bit_count, value as u64)
+    }
+
+    pub fn write_u16(&mut self, bit_count: u8, value: u16) -> Result<()> {
+        try!(self.check_bit_count(bit_count, 16));
+        self.write_u64(bit_count, value as u64)
+    }
+
+    pub fn write_u32(&mut self, bit_count: u8, value: u32) -> Result<()> {
+        try!(self.check_bit_count(bit_count, 32));
+        self.write_u64(bit_count, value as u64)
+    }
+
+    pub fn write_u64(&mut self, bit_count: u8, value: u64) -> Result<()> {
+        try!(self.check_bit_count(bit_count, 64));
+        if bit_count < 64 && value >> bit_count != 0 {
+            return Err(BitWriterError::ValueTooLarge {
+                position: self.position,
+                length: bit_count,
+                value: value,
+            });
+        }
+        for bit in (0..bit_count).rev() {
+            let byte_index = (self.position / 8) as usize;
+            if byte_index == self.bytes.len() {
+                self.bytes.push(0);
+            }
+            if value >> bit & 1 == 1 {
+                self.bytes[byte_index] |= 0x80 >> (self.position % 8);
+            }
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    pub fn write_bool(&mut self, value: bool) -> Result<()> {
+        self.write_u64(1, if value { 1 } else { 0 })
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        for byte in bytes {
+            try!(self.write_u8(8, *byte));
+        }
+        Ok(())
+    }
+
+    /// Appends everything written to another writer.
+    pub fn append(&mut self, other: &BitWriter) -> Result<()> {
+        let whole_bytes = (other.position / 8) as usize;
+        try!(self.write_bytes(&other.bytes[..whole_bytes]));
+        let rest = (other.position % 8) as u8;
+        if rest > 0 {
+            try!(self.write_u8(rest, other.bytes[whole_bytes] >> (8 - rest)));
+        }
+        Ok(())
+    }
+
+    /// Writes a placeholder for a length field that counts the bytes from the end of the field to
+    /// the point where `close_lengths` is called.
+    pub fn open_length(&mut self, bit_count: u8) -> Result<()> {
+        self.open_lengths.push((self.position, bit_count));
+        self.write_u64(bit_count, 0)
+    }
+
+    /// Fills in the open length fields, counting `extra_bytes` that are not written yet.
+    pub fn close_lengths(&mut self, extra_bytes: u64) -> Result<()> {
+        while let Some((position, bit_count)) = self.open_lengths.pop() {
+            let length = (self.position - position - bit_count as u64) / 8 + extra_bytes;
+            if bit_count < 64 && length >> bit_count != 0 {
+                return Err(BitWriterError::LengthTooLarge {
+                    position: position,
+                    length: bit_count,
+                    value: length,
+                });
+            }
+            let end = self.position;
+            self.position = position;
+            for bit in (0..bit_count).rev() {
+                let mask = 0x80 >> (self.position % 8);
+                let byte = &mut self.bytes[(self.position / 8) as usize];
+                if length >> bit & 1 == 1 {
+                    *byte |= mask;
+                } else {
+                    *byte &= !mask;
+                }
+                self.position += 1;
+            }
+            self.position = end;
+        }
+        Ok(())
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn is_aligned(&self) -> bool {
+        self.position % 8 == 0
+    }
+
+    /// The bytes written so far. A partially written last byte is padded with zero bits.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..]
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    fn check_bit_count(&self, bit_count: u8, allowed: u8) -> Result<()> {
+        if bit_count > allowed {
+            return Err(BitWriterError::TooManyBitsForType {
+                position: self.position,
+                requested: bit_count,
+                allowed: allowed,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Values that can be written as a bit field, the counterpart of `bitreader::ReadInto`.
+pub trait WriteInto {
+    fn write(&self, writer: &mut BitWriter, bits: u8) -> Result<()>;
+}
+
+impl WriteInto for u8 {
+    fn write(&self, writer: &mut BitWriter, bits: u8) -> Result<()> {
+        writer.write_u8(bits, *self)
+    }
+}
+
+impl WriteInto for u16 {
+    fn write(&self, writer: &mut BitWriter, bits: u8) -> Result<()> {
+        writer.write_u16(bits, *self)
+    }
+}
+
+impl WriteInto for u32 {
+    fn write(&self, writer: &mut BitWriter, bits: u8) -> Result<()> {
+        writer.write_u32(bits, *self)
+    }
+}
+
+impl WriteInto for u64 {
+    fn write(&self, writer: &mut BitWriter, bits: u8) -> Result<()> {
+        writer.write_u64(bits, *self)
+    }
+}
+
+impl WriteInto for bool {
+    fn write(&self, writer: &mut BitWriter, bits: u8) -> Result<()> {
+        writer.write_u64(bits, if *self { 1 } else { 0 })
+    }
+}
+
+impl<'a, T: WriteInto> WriteInto for &'a T {
+    fn write(&self, writer: &mut BitWriter, bits: u8) -> Result<()> {
+        (**self).write(writer, bits)
+    }
+}
@@ -0,0 +1,325 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Program clock recovery
+//!
+//! Program time is expressed in 27 MHz system clock ticks. PTS and DTS are in 90 kHz units, so
+//! one timestamp unit is 300 ticks.
+
+use ::ts::{TsPacket,PACKET_SIZE};
+use ::pes::PesHeader;
+use ::section::psi::ProgramMapSection;
+
+pub const SYSTEM_CLOCK_FREQUENCY: i64 = 27_000_000;
+pub const TIMESTAMP_FREQUENCY: i64 = 90_000;
+
+/// PTS, DTS and PCR base are 33-bit counters
+pub const TIMESTAMP_MODULUS: i64 = 1 << 33;
+/// PCR including the 9-bit extension, which counts from 0 to 299
+pub const PCR_MODULUS: i64 = TIMESTAMP_MODULUS * 300;
+
+// PCRs arrive at most 100 ms apart, so a PCR more than a second away from its expected value is
+// a discontinuity even if it is not signalled
+const MAX_PCR_JUMP: i64 = SYSTEM_CLOCK_FREQUENCY;
+
+/// Turns values of a wrapping counter into a continuous 64-bit timeline, assuming consecutive
+/// values are less than half of the counter range apart.
+#[derive(Debug,Clone,Copy)]
+pub struct Unwrapper {
+    modulus: i64,
+    last: Option<i64>,
+}
+
+impl Unwrapper {
+    pub fn new(modulus: i64) -> Unwrapper {
+        Unwrapper {
+            modulus: modulus,
+            last: None,
+        }
+    }
+
+    /// Unwrapper for PTS and DTS values.
+    pub fn timestamps() -> Unwrapper {
+        Unwrapper::new(TIMESTAMP_MODULUS)
+    }
+
+    /// Unwrapper for PCR values in 27 MHz ticks.
+    pub fn pcr() -> Unwrapper {
+        Unwrapper::new(PCR_MODULUS)
+    }
+
+    pub fn unwrap(&mut self, value: u64) -> i64 {
+        let unwrapped = match self.last {
+            Some(last) => unwrap_near(value, last, self.modulus),
+            None => value as i64 % self.modulus,
+        };
+        self.last = Some(unwrapped);
+        unwrapped
+    }
+
+    /// Start a new timeline from the next value.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/// The value congruent to `value` that is nearest to `reference`.
+pub fn unwrap_near(value: u64, reference: i64, modulus: i64) -> i64 {
+    let reference_wrapped = (reference % modulus + modulus) % modulus;
+    let mut difference = value as i64 % modulus - reference_wrapped;
+    if difference >= modulus / 2 {
+        difference -= modulus;
+    } else if difference < -modulus / 2 {
+        difference += modulus;
+    }
+    reference + difference
+}
+
+#[derive(Debug,Clone,Copy)]
+struct PcrSample {
+    packet_index: u64,
+    time: i64,
+}
+
+/// Recovers the clock of a program from the PCRs on its `pcr_pid`. All packets of the transport
+/// stream must be pushed in order, as the times of packets between PCRs are interpolated from
+/// their positions.
+///
+/// Program time is continuous over discontinuities, whether signalled with
+/// `discontinuity_indicator` or not: the PCR after a discontinuity continues the timeline from the
+/// time the packet was expected to have.
+#[derive(Debug,Clone)]
+pub struct ProgramClock {
+    pcr_pid: u16,
+    packet_count: u64,
+    pcr_unwrapper: Unwrapper,
+    // Difference between the program timeline and unwrapped PCRs of the current timebase
+    offset: i64,
+    last_pcr: Option<PcrSample>,
+    ticks_per_packet: Option<f64>,
+    // The next PCR starts a new timebase
+    pending_discontinuity: bool,
+}
+
+impl ProgramClock {
+    pub fn new(pcr_pid: u16) -> ProgramClock {
+        ProgramClock {
+            pcr_pid: pcr_pid,
+            packet_count: 0,
+            pcr_unwrapper: Unwrapper::pcr(),
+            offset: 0,
+            last_pcr: None,
+            ticks_per_packet: None,
+            pending_discontinuity: false,
+        }
+    }
+
+    pub fn from_program_map(program_map: &ProgramMapSection) -> ProgramClock {
+        ProgramClock::new(program_map.pcr_pid)
+    }
+
+    pub fn pcr_pid(&self) -> u16 {
+        self.pcr_pid
+    }
+
+    /// Follow a new version of the program map. A change of pcr_pid is handled as a
+    /// discontinuity.
+    pub fn update_program_map(&mut self, program_map: &ProgramMapSection) {
+        if program_map.pcr_pid != self.pcr_pid {
+            self.pcr_pid = program_map.pcr_pid;
+            self.pending_discontinuity = true;
+        }
+    }
+
+    /// Feed the next packet of the transport stream, returning the program time estimate of the
+    /// packet. There is no estimate before the first PCR, and packets other than the first PCR
+    /// have none until the rate between two PCRs is known.
+    pub fn push(&mut self, packet: &TsPacket) -> Option<i64> {
+        let packet_index = self.packet_count;
+        self.packet_count += 1;
+        if packet.pid == self.pcr_pid {
+            if let Some(ref adaptation_field) = packet.adaptation_field {
+                if let Some(pcr) = adaptation_field.pcr {
+                    return Some(self.push_pcr(packet_index, pcr.ticks(), adaptation_field.discontinuity_indicator));
+                }
+            }
+        }
+        self.estimate(packet_index)
+    }
+
+    fn push_pcr(&mut self, packet_index: u64, pcr: u64, discontinuity: bool) -> i64 {
+        let estimate = self.estimate(packet_index);
+        let discontinuity = discontinuity || self.pending_discontinuity;
+        self.pending_discontinuity = false;
+        if discontinuity {
+            self.pcr_unwrapper.reset();
+        }
+        let unwrapped = self.pcr_unwrapper.unwrap(pcr);
+        let mut time = unwrapped + self.offset;
+        if let Some(last) = self.last_pcr {
+            let expected = estimate.unwrap_or(last.time);
+            if discontinuity || (time - expected).abs() > MAX_PCR_JUMP {
+                self.offset = expected - unwrapped;
+                time = expected;
+            } else if packet_index > last.packet_index {
+                let ticks = (time - last.time) as f64;
+                self.ticks_per_packet = Some(ticks / (packet_index - last.packet_index) as f64);
+            }
+        }
+        self.last_pcr = Some(PcrSample {
+            packet_index: packet_index,
+            time: time,
+        });
+        time
+    }
+
+    fn estimate(&self, packet_index: u64) -> Option<i64> {
+        match (self.last_pcr, self.ticks_per_packet) {
+            (Some(last), Some(ticks_per_packet)) => {
+                let packets = packet_index as f64 - last.packet_index as f64;
+                Some(last.time + (packets * ticks_per_packet).round() as i64)
+            },
+            _ => None,
+        }
+    }
+
+    /// Program time of the most recent PCR.
+    pub fn last_pcr_time(&self) -> Option<i64> {
+        self.last_pcr.map(|last| last.time)
+    }
+
+    /// Transport stream rate in bits per second, measured between the two most recent PCRs.
+    pub fn transport_rate(&self) -> Option<u64> {
+        self.ticks_per_packet.and_then(|ticks_per_packet| {
+            if ticks_per_packet > 0.0 {
+                let bits = (PACKET_SIZE * 8) as f64;
+                Some((bits * SYSTEM_CLOCK_FREQUENCY as f64 / ticks_per_packet).round() as u64)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Program time of a PTS or DTS. Timestamps are close to the PCR of their program, which
+    /// resolves their wraparound, so at least one PCR is needed.
+    pub fn timestamp_time(&self, timestamp: u64) -> Option<i64> {
+        self.last_pcr.map(|last| {
+            let reference = (last.time - self.offset) / 300;
+            unwrap_near(timestamp, reference, TIMESTAMP_MODULUS) * 300 + self.offset
+        })
+    }
+
+    pub fn presentation_time(&self, header: &PesHeader) -> Option<i64> {
+        header.pts.and_then(|pts| self.timestamp_time(pts))
+    }
+
+    /// Decoding time is given by DTS, or PTS when they are equal.
+    pub fn decoding_time(&self, header: &PesHeader) -> Option<i64> {
+        header.dts.or(header.pts).and_then(|timestamp| self.timestamp_time(timestamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::ts::{TsPacket,AdaptationField,ClockReference};
+    use super::*;
+
+    // Packet on PID 0x100, carrying a PCR when given
+    fn packet(pcr: Option<i64>, discontinuity_indicator: bool) -> TsPacket {
+        TsPacket {
+            transport_error_indicator: false,
+            payload_unit_start_indicator: false,
+            transport_priority: false,
+            pid: 0x100,
+            transport_scrambling_control: 0,
+            adaptation_field_control: if pcr.is_some() { 0b11 } else { 0b01 },
+            continuity_counter: 0,
+            adaptation_field: pcr.map(|pcr| AdaptationField {
+                discontinuity_indicator: discontinuity_indicator,
+                random_access_indicator: false,
+                elementary_stream_priority_indicator: false,
+                pcr: Some(ClockReference {
+                    base: (pcr / 300) as u64,
+                    extension: (pcr % 300) as u16,
+                }),
+                opcr: None,
+                splice_countdown: None,
+                transport_private_data: None,
+                extension: None,
+            }),
+            payload: vec![],
+        }
+    }
+
+    #[test]
+    fn unwrapping() {
+        assert_eq!(unwrap_near(5, TIMESTAMP_MODULUS - 5, TIMESTAMP_MODULUS), TIMESTAMP_MODULUS + 5);
+        assert_eq!(unwrap_near((TIMESTAMP_MODULUS - 5) as u64, 5, TIMESTAMP_MODULUS), -5);
+        assert_eq!(unwrap_near(100, 50, TIMESTAMP_MODULUS), 100);
+        assert_eq!(unwrap_near(100, 3 * TIMESTAMP_MODULUS + 50, TIMESTAMP_MODULUS), 3 * TIMESTAMP_MODULUS + 100);
+
+        let mut unwrapper = Unwrapper::timestamps();
+        assert_eq!(unwrapper.unwrap((TIMESTAMP_MODULUS - 100) as u64), TIMESTAMP_MODULUS - 100);
+        assert_eq!(unwrapper.unwrap(50), TIMESTAMP_MODULUS + 50);
+        assert_eq!(unwrapper.unwrap(200), TIMESTAMP_MODULUS + 200);
+        assert_eq!(unwrapper.unwrap((TIMESTAMP_MODULUS - 100) as u64), TIMESTAMP_MODULUS - 100);
+        unwrapper.reset();
+        assert_eq!(unwrapper.unwrap(10), 10);
+    }
+
+    #[test]
+    fn program_clock() {
+        // A PCR every ten packets, 1000 ticks apart per packet, wrapping between the first two
+        let mut clock = ProgramClock::new(0x100);
+        assert_eq!(clock.push(&packet(None, false)), None);
+        assert_eq!(clock.push(&packet(Some(PCR_MODULUS - 9000), false)), Some(PCR_MODULUS - 9000));
+        for _ in 0..9 {
+            assert_eq!(clock.push(&packet(None, false)), None);
+        }
+        assert_eq!(clock.push(&packet(Some(1000), false)), Some(PCR_MODULUS + 1000));
+        assert_eq!(clock.push(&packet(None, false)), Some(PCR_MODULUS + 2000));
+        assert_eq!(clock.transport_rate(), Some(188 * 8 * 27_000));
+
+        // A PTS slightly after the PCR, in the 33-bit timestamp range following the wraparound
+        assert_eq!(clock.timestamp_time(100), Some(PCR_MODULUS + 100 * 300));
+        assert_eq!(clock.timestamp_time((TIMESTAMP_MODULUS - 10) as u64), Some(PCR_MODULUS - 10 * 300));
+    }
+
+    #[test]
+    fn discontinuities() {
+        let mut clock = ProgramClock::new(0x100);
+        clock.push(&packet(Some(0), false));
+        for _ in 0..9 {
+            clock.push(&packet(None, false));
+        }
+        assert_eq!(clock.push(&packet(Some(10000), false)), Some(10000));
+        for _ in 0..9 {
+            clock.push(&packet(None, false));
+        }
+        // A signalled discontinuity continues the timeline from the expected time
+        assert_eq!(clock.push(&packet(Some(5_000_000), true)), Some(20000));
+        for _ in 0..9 {
+            clock.push(&packet(None, false));
+        }
+        assert_eq!(clock.push(&packet(Some(5_010_000), false)), Some(30000));
+        // So does a jump of more than a second that is not signalled
+        for _ in 0..9 {
+            clock.push(&packet(None, false));
+        }
+        assert_eq!(clock.push(&packet(Some(300_000_000), false)), Some(40000));
+        assert_eq!(clock.last_pcr_time(), Some(40000));
+        // Timestamps follow the timebase of the latest PCR
+        assert_eq!(clock.timestamp_time(300_000_000 / 300 + 10), Some(40000 + 10 * 300));
+    }
+}
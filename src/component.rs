@@ -0,0 +1,260 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! # Classifying elementary streams
+//!
+//! The stream_type of a PMT entry alone does not tell what a stream carries: DVB signals AC-3,
+//! subtitles and teletext as private PES packets (stream_type 0x06) with a descriptor telling
+//! them apart, and user private stream types are identified with a registration descriptor.
+
+use ::descriptor::{Descriptor,find_descriptor};
+use ::descriptor::basic::RegistrationDescriptor;
+use ::descriptor::dvb::{Ac3Descriptor,EnhancedAc3Descriptor,DtsDescriptor,AacDescriptor,
+    SubtitlingDescriptor,TeletextDescriptor,VbiTeletextDescriptor};
+
+coded_enum!(
+    /// stream_type of an elementary stream, ISO/IEC 13818-1 Table 2-34.
+    pub enum StreamType {
+        0x01 => Mpeg1Video: "ISO/IEC 11172-2 video",
+        0x02 => Mpeg2Video: "ITU-T H.262 / ISO/IEC 13818-2 video",
+        0x03 => Mpeg1Audio: "ISO/IEC 11172-3 audio",
+        0x04 => Mpeg2Audio: "ISO/IEC 13818-3 audio",
+        0x05 => PrivateSections: "private sections",
+        0x06 => PrivatePesPackets: "PES packets containing private data",
+        0x07 => Mheg: "ISO/IEC 13522 MHEG",
+        0x08 => DsmCc: "ISO/IEC 13818-1 Annex A DSM-CC",
+        0x09 => H2221: "ITU-T H.222.1",
+        0x0a => DsmCcMultiprotocolEncapsulation: "ISO/IEC 13818-6 type A",
+        0x0b => DsmCcUnMessages: "ISO/IEC 13818-6 type B",
+        0x0c => DsmCcStreamDescriptors: "ISO/IEC 13818-6 type C",
+        0x0d => DsmCcSections: "ISO/IEC 13818-6 type D",
+        0x0e => Auxiliary: "ISO/IEC 13818-1 auxiliary",
+        0x0f => AdtsAudio: "ISO/IEC 13818-7 audio with ADTS transport syntax",
+        0x10 => Mpeg4Visual: "ISO/IEC 14496-2 visual",
+        0x11 => LatmAudio: "ISO/IEC 14496-3 audio with LATM transport syntax",
+        0x12 => SlPesPackets: "ISO/IEC 14496-1 SL-packetized or FlexMux stream in PES packets",
+        0x13 => SlSections: "ISO/IEC 14496-1 SL-packetized or FlexMux stream in sections",
+        0x14 => DsmCcSynchronizedDownload: "ISO/IEC 13818-6 synchronized download protocol",
+        0x15 => MetadataPesPackets: "metadata in PES packets",
+        0x16 => MetadataSections: "metadata in metadata sections",
+        0x17 => MetadataDataCarousel: "metadata in ISO/IEC 13818-6 data carousel",
+        0x18 => MetadataObjectCarousel: "metadata in ISO/IEC 13818-6 object carousel",
+        0x19 => MetadataSynchronizedDownload: "metadata in ISO/IEC 13818-6 synchronized download protocol",
+        0x1a => Ipmp: "ISO/IEC 13818-11 IPMP stream",
+        0x1b => H264: "AVC video, ITU-T H.264 / ISO/IEC 14496-10",
+        0x1c => Mpeg4RawAudio: "ISO/IEC 14496-3 audio without additional transport syntax",
+        0x1d => Mpeg4Text: "ISO/IEC 14496-17 text",
+        0x1e => AuxiliaryVideo: "ISO/IEC 23002-3 auxiliary video",
+        0x1f => SvcSubBitstream: "SVC video sub-bitstream of H.264",
+        0x20 => MvcSubBitstream: "MVC video sub-bitstream of H.264",
+        0x21 => Jpeg2000Video: "ITU-T T.800 / ISO/IEC 15444-1 JPEG 2000 video",
+        0x22 => Mpeg2StereoscopicVideo: "additional view of H.262 service-compatible stereoscopic 3D video",
+        0x23 => H264StereoscopicVideo: "additional view of H.264 service-compatible stereoscopic 3D video",
+        0x24 => Hevc: "HEVC video, ITU-T H.265 / ISO/IEC 23008-2",
+        0x25 => HevcTemporalSubset: "HEVC temporal video subset",
+        0x7f => IpmpStream: "IPMP stream"
+    }
+    ranges {
+        // Such as AC-3 (0x81) and SCTE 35 (0x86) in ATSC
+        0x80...0xff => UserPrivate: "user private",
+        _ => Reserved: "reserved"
+    }
+);
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum VideoCodec {
+    Mpeg1,
+    Mpeg2,
+    Mpeg4Visual,
+    H264,
+    Hevc,
+    Jpeg2000,
+    Vc1,
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum AudioCodec {
+    Mpeg1,
+    Mpeg2,
+    /// AAC with ADTS transport syntax
+    Aac,
+    /// AAC or HE-AAC with LATM transport syntax
+    AacLatm,
+    /// MPEG-4 audio without an additional transport syntax
+    Mpeg4Raw,
+    Ac3,
+    EnhancedAc3,
+    Dts,
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum ComponentKind {
+    Video(VideoCodec),
+    Audio(AudioCodec),
+    /// DVB subtitles, ETSI EN 300 743
+    Subtitles,
+    /// EBU teletext, which may also carry subtitles
+    Teletext,
+    /// SCTE 35 splice information
+    Scte35,
+    /// Sections, DSM-CC, metadata and other data streams
+    Data,
+    Unknown,
+}
+
+/// Classifies an elementary stream from its stream_type and the descriptors of its ES_info loop.
+pub fn classify_component(stream_type: StreamType, descriptors: &[Box<Descriptor>]) -> ComponentKind {
+    match stream_type {
+        StreamType::Mpeg1Video => ComponentKind::Video(VideoCodec::Mpeg1),
+        StreamType::Mpeg2Video |
+        StreamType::Mpeg2StereoscopicVideo => ComponentKind::Video(VideoCodec::Mpeg2),
+        StreamType::Mpeg4Visual => ComponentKind::Video(VideoCodec::Mpeg4Visual),
+        StreamType::H264 |
+        StreamType::SvcSubBitstream |
+        StreamType::MvcSubBitstream |
+        StreamType::H264StereoscopicVideo => ComponentKind::Video(VideoCodec::H264),
+        StreamType::Hevc |
+        StreamType::HevcTemporalSubset => ComponentKind::Video(VideoCodec::Hevc),
+        StreamType::Jpeg2000Video => ComponentKind::Video(VideoCodec::Jpeg2000),
+        StreamType::Mpeg1Audio => ComponentKind::Audio(AudioCodec::Mpeg1),
+        StreamType::Mpeg2Audio => ComponentKind::Audio(AudioCodec::Mpeg2),
+        StreamType::AdtsAudio => ComponentKind::Audio(AudioCodec::Aac),
+        StreamType::LatmAudio => ComponentKind::Audio(AudioCodec::AacLatm),
+        StreamType::Mpeg4RawAudio => ComponentKind::Audio(AudioCodec::Mpeg4Raw),
+        StreamType::PrivateSections |
+        StreamType::Mheg |
+        StreamType::DsmCc |
+        StreamType::DsmCcMultiprotocolEncapsulation |
+        StreamType::DsmCcUnMessages |
+        StreamType::DsmCcStreamDescriptors |
+        StreamType::DsmCcSections |
+        StreamType::DsmCcSynchronizedDownload |
+        StreamType::MetadataPesPackets |
+        StreamType::MetadataSections |
+        StreamType::MetadataDataCarousel |
+        StreamType::MetadataObjectCarousel |
+        StreamType::MetadataSynchronizedDownload |
+        StreamType::SlSections => ComponentKind::Data,
+        StreamType::PrivatePesPackets => {
+            private_stream_kind(descriptors).unwrap_or(ComponentKind::Unknown)
+        },
+        // ATSC A/52 and SCTE 35 stream types, which are also used in DVB networks
+        StreamType::UserPrivate(value) => {
+            private_stream_kind(descriptors).unwrap_or(match value {
+                0x81 => ComponentKind::Audio(AudioCodec::Ac3),
+                0x86 => ComponentKind::Scte35,
+                0x87 => ComponentKind::Audio(AudioCodec::EnhancedAc3),
+                _ => ComponentKind::Unknown,
+            })
+        },
+        _ => ComponentKind::Unknown,
+    }
+}
+
+// Kind of a private stream from DVB descriptors (ETSI EN 300 468 Annex D) or a registration
+// descriptor
+fn private_stream_kind(descriptors: &[Box<Descriptor>]) -> Option<ComponentKind> {
+    if find_descriptor::<Ac3Descriptor>(descriptors).is_some() {
+        Some(ComponentKind::Audio(AudioCodec::Ac3))
+    } else if find_descriptor::<EnhancedAc3Descriptor>(descriptors).is_some() {
+        Some(ComponentKind::Audio(AudioCodec::EnhancedAc3))
+    } else if find_descriptor::<DtsDescriptor>(descriptors).is_some() {
+        Some(ComponentKind::Audio(AudioCodec::Dts))
+    } else if find_descriptor::<AacDescriptor>(descriptors).is_some() {
+        Some(ComponentKind::Audio(AudioCodec::Aac))
+    } else if find_descriptor::<SubtitlingDescriptor>(descriptors).is_some() {
+        Some(ComponentKind::Subtitles)
+    } else if find_descriptor::<TeletextDescriptor>(descriptors).is_some() ||
+              find_descriptor::<VbiTeletextDescriptor>(descriptors).is_some() {
+        Some(ComponentKind::Teletext)
+    } else {
+        find_descriptor::<RegistrationDescriptor>(descriptors)
+            .and_then(|registration| registered_kind(registration.format_identifier))
+    }
+}
+
+// Format identifiers registered with the SMPTE Registration Authority
+fn registered_kind(format_identifier: u32) -> Option<ComponentKind> {
+    let bytes = [
+        (format_identifier >> 24) as u8,
+        (format_identifier >> 16) as u8,
+        (format_identifier >> 8) as u8,
+        format_identifier as u8,
+    ];
+    match &bytes {
+        b"AC-3" => Some(ComponentKind::Audio(AudioCodec::Ac3)),
+        b"EAC3" => Some(ComponentKind::Audio(AudioCodec::EnhancedAc3)),
+        b"DTS1" | b"DTS2" | b"DTS3" => Some(ComponentKind::Audio(AudioCodec::Dts)),
+        b"HEVC" => Some(ComponentKind::Video(VideoCodec::Hevc)),
+        b"VC-1" => Some(ComponentKind::Video(VideoCodec::Vc1)),
+        b"CUEI" => Some(ComponentKind::Scte35),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitreader::BitReader;
+    use super::*;
+    use ::descriptor::deserialize_descriptor;
+
+    fn descriptors(bytes: &[u8]) -> Vec<Box<Descriptor>> {
+        let mut reader = BitReader::new(bytes);
+        let mut descriptors = vec![];
+        while reader.remaining() > 0 {
+            descriptors.push(deserialize_descriptor(&mut reader).unwrap());
+        }
+        descriptors
+    }
+
+    #[test]
+    fn stream_type_values() {
+        assert_eq!(StreamType::from(0x24), StreamType::Hevc);
+        assert_eq!(StreamType::from(0x40), StreamType::Reserved(0x40));
+        assert_eq!(StreamType::from(0x86), StreamType::UserPrivate(0x86));
+        for value in 0..256 {
+            assert_eq!(u8::from(StreamType::from(value as u8)), value as u8);
+        }
+        assert_eq!(StreamType::UserPrivate(0x86).to_string(), "user private (0x86)");
+    }
+
+    #[test]
+    fn stream_types() {
+        assert_eq!(classify_component(StreamType::from(0x02), &[]), ComponentKind::Video(VideoCodec::Mpeg2));
+        assert_eq!(classify_component(StreamType::from(0x1b), &[]), ComponentKind::Video(VideoCodec::H264));
+        assert_eq!(classify_component(StreamType::from(0x0f), &[]), ComponentKind::Audio(AudioCodec::Aac));
+        assert_eq!(classify_component(StreamType::from(0x1c), &[]), ComponentKind::Audio(AudioCodec::Mpeg4Raw));
+        assert_eq!(classify_component(StreamType::from(0x05), &[]), ComponentKind::Data);
+        assert_eq!(classify_component(StreamType::from(0x06), &[]), ComponentKind::Unknown);
+        assert_eq!(classify_component(StreamType::from(0x86), &[]), ComponentKind::Scte35);
+    }
+
+    #[test]
+    fn private_streams() {
+        // AC-3 descriptor without optional fields
+        let ac3 = descriptors(&[0x6a, 0x01, 0x0f]);
+        assert_eq!(classify_component(StreamType::from(0x06), &ac3), ComponentKind::Audio(AudioCodec::Ac3));
+        // Teletext descriptor with an initial page for "fin"
+        let teletext = descriptors(&[0x56, 0x05, 0x66, 0x69, 0x6e, 0x09, 0x00]);
+        assert_eq!(classify_component(StreamType::from(0x06), &teletext), ComponentKind::Teletext);
+        // Registration descriptor for "EAC3"
+        let registration = descriptors(&[0x05, 0x04, 0x45, 0x41, 0x43, 0x33]);
+        assert_eq!(classify_component(StreamType::from(0x06), &registration),
+                   ComponentKind::Audio(AudioCodec::EnhancedAc3));
+        assert_eq!(classify_component(StreamType::from(0x81), &registration),
+                   ComponentKind::Audio(AudioCodec::EnhancedAc3));
+        assert_eq!(classify_component(StreamType::from(0x81), &[]), ComponentKind::Audio(AudioCodec::Ac3));
+    }
+}
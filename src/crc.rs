@@ -0,0 +1,52 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// CRC-32/MPEG-2, as specified in ISO/IEC 13818-1 Annex A: polynomial 0x04c11db7, initial value
+// 0xffffffff, no reflection and no final XOR.
+const POLYNOMIAL: u32 = 0x04c11db7;
+
+pub fn crc32_mpeg2(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffffffff;
+    for byte in bytes {
+        crc ^= (*byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x80000000 != 0 {
+                (crc << 1) ^ POLYNOMIAL
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_value() {
+        assert_eq!(crc32_mpeg2(b"123456789"), 0x0376e6e7);
+        assert_eq!(crc32_mpeg2(&[]), 0xffffffff);
+    }
+
+    #[test]
+    fn section_crc() {
+        let pat = [0x00, 0xb0, 0x0d, 0x00, 0x01, 0xc1, 0x00, 0x00, 0x00, 0x01, 0xe1, 0x00,
+            0xe8, 0xf9, 0x5e, 0x7d];
+        assert_eq!(crc32_mpeg2(&pat[..12]), 0xe8f95e7d);
+        // The CRC over a whole section including its CRC_32 is zero
+        assert_eq!(crc32_mpeg2(&pat), 0);
+    }
+}
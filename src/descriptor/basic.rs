@@ -12,8 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::any::Any;
+
 use ::base::{Deserialize,bool_flag};
-use super::{Descriptor,bits_remaining,repeated_element,read_tla};
+use super::{Descriptor,bits_remaining,repeated_element,read_tla,tla_bytes};
 
 bit_struct!(
     #[derive(Debug)]
@@ -24,7 +26,7 @@ bit_struct!(
     }
     deserialize(reader) {
         descriptor_tag: { 8 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         data: { value: {
             let mut data = vec![];
             for _ in 0..descriptor_length {
@@ -35,7 +37,15 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for UnknownDescriptor {}
+impl Descriptor for UnknownDescriptor {
+    fn tag(&self) -> u8 {
+        self.descriptor_tag
+    }
+
+    fn as_any(&self) -> &Any {
+        self
+    }
+}
 
 
 bit_struct!(
@@ -64,7 +74,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 2 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         multiple_frame_rate: { 1, map: bool_flag },
         frame_rate_code: { 4 },
         mpeg_1_only: { 1, map: bool_flag },
@@ -79,7 +89,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for VideoStreamDescriptor {}
+impl_descriptor!(VideoStreamDescriptor, 2);
 
 
 bit_struct!(
@@ -92,7 +102,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 3 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         free_format: { 1, map: bool_flag },
         id: { 1, map: bool_flag },
         layer: { 2 },
@@ -101,7 +111,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for AudioStreamDescriptor {}
+impl_descriptor!(AudioStreamDescriptor, 3);
 
 
 bit_struct!(
@@ -114,7 +124,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 4 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         reserved: { 4 },
         hierarchy_type: { 4 },
         reserved: { 2 },
@@ -126,7 +136,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for HierarchyDescriptor {}
+impl_descriptor!(HierarchyDescriptor, 4);
 
 
 bit_struct!(
@@ -137,7 +147,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits:8, reference: 5 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         format_identifier: { 32 },
         additional_identification_info: { value: {
             let mut data = vec![];
@@ -149,7 +159,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for RegistrationDescriptor {}
+impl_descriptor!(RegistrationDescriptor, 5);
 
 
 bit_struct!(
@@ -159,12 +169,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 6 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         alignment_type: { 8 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for DataStreamAlignmentDescriptor {}
+impl_descriptor!(DataStreamAlignmentDescriptor, 6);
 
 
 bit_struct!(
@@ -176,14 +186,14 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 7 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         horizontal_size: { 14 },
         vertical_size: { 14 },
         aspect_ration_information: { 4 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for TargetBackgroundGridDescriptor {}
+impl_descriptor!(TargetBackgroundGridDescriptor, 7);
 
 
 bit_struct!(
@@ -195,14 +205,14 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 8 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         horizontal_offset: { 14 },
         vertical_offset: { 14 },
         window_priority: { 4 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for VideoWindowDescriptor {}
+impl_descriptor!(VideoWindowDescriptor, 8);
 
 
 bit_struct!(
@@ -214,7 +224,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits:8, reference: 9 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         ca_system_id: { 16 },
         reserved: { 3 },
         ca_pid: { 13 },
@@ -228,7 +238,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for CaDescriptor {}
+impl_descriptor!(CaDescriptor, 9);
 
 
 bit_struct!(
@@ -238,7 +248,7 @@ bit_struct!(
         pub audio_type: u8
     }
     deserialize(reader) {
-        language: { value: try!(read_tla(reader)) },
+        language: { value: try!(read_tla(reader)), write: try!(tla_bytes(language)) },
         audio_type: { 8 }
     }
 );
@@ -250,12 +260,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 10 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         languages: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for Iso639LanguageDescriptor {}
+impl_descriptor!(Iso639LanguageDescriptor, 10);
 
 
 bit_struct!(
@@ -267,7 +277,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 11 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         external_clock_reference: { 1, map: bool_flag },
         reserved: { 1 },
         clock_accuracy_integer: { 6 },
@@ -276,7 +286,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for SystemClockDescriptor {}
+impl_descriptor!(SystemClockDescriptor, 11);
 
 
 bit_struct!(
@@ -288,7 +298,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 12 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         bound_valid: { 1, map: bool_flag },
         ltw_offset_lower_bound: { 15 },
         reserved: { 1 },
@@ -296,7 +306,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for MultiplexBufferUtilizationDescriptor {}
+impl_descriptor!(MultiplexBufferUtilizationDescriptor, 12);
 
 
 bit_struct!(
@@ -307,7 +317,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits:8, reference: 13 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         copyright_identifier: { 8 },
         additional_copyright_info: { value: {
             let mut data = vec![];
@@ -319,7 +329,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for CopyrightDescriptor {}
+impl_descriptor!(CopyrightDescriptor, 13);
 
 
 bit_struct!(
@@ -329,13 +339,13 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 14 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         reserved: { 2 },
         maximum_bitrate: { 22 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for MaximumBitrateDescriptor {}
+impl_descriptor!(MaximumBitrateDescriptor, 14);
 
 
 bit_struct!(
@@ -345,12 +355,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 15 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         private_data_indicator: { 32 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for PrivateDataIndicatorDescriptor {}
+impl_descriptor!(PrivateDataIndicatorDescriptor, 15);
 
 
 bit_struct!(
@@ -361,7 +371,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 16 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         reserved: { 2 },
         leak_rate: { 22 },
         reserved: { 2 },
@@ -369,7 +379,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for SmoothingBufferDescriptor {}
+impl_descriptor!(SmoothingBufferDescriptor, 16);
 
 
 bit_struct!(
@@ -379,13 +389,13 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 17 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         reserved: { 7 },
         leak_valid: { 1, map: bool_flag },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for StdDescriptor {}
+impl_descriptor!(StdDescriptor, 17);
 
 
 bit_struct!(
@@ -397,14 +407,14 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 18 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         closed_gop: { 1, map: bool_flag },
         identical_gop: { 1, map: bool_flag },
         max_gop_length: { 14 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for IbpDescriptor {}
+impl_descriptor!(IbpDescriptor, 18);
 
 
 bit_struct!(
@@ -414,12 +424,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 27 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         visual_profile_and_level: { 8 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for Mpeg4VideoDescriptor {}
+impl_descriptor!(Mpeg4VideoDescriptor, 27);
 
 
 bit_struct!(
@@ -429,12 +439,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 28 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         audio_profile_and_level: { 8 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for Mpeg4AudioDescriptor {}
+impl_descriptor!(Mpeg4AudioDescriptor, 28);
 
 
 bit_struct!(
@@ -446,14 +456,14 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 29 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         scope_of_iod_label: { 8 },
         iod_label: { 8 },
         // TODO: Missing initial object descriptor fields
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for IodDescriptor {}
+impl_descriptor!(IodDescriptor, 29);
 
 
 bit_struct!(
@@ -463,12 +473,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 30 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         es_id: { 16 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for SlDescriptor {}
+impl_descriptor!(SlDescriptor, 30);
 
 
 bit_struct!(
@@ -490,12 +500,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 31 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         channels: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for FmcDescriptor {}
+impl_descriptor!(FmcDescriptor, 31);
 
 
 bit_struct!(
@@ -505,12 +515,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 32 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         external_es_id: { 16 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ExternalEsIdDescriptor {}
+impl_descriptor!(ExternalEsIdDescriptor, 32);
 
 
 bit_struct!(
@@ -521,13 +531,13 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 33 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         empty: { value: () },
         // TODO: Missing descriptor fields
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for MuxCodeDescriptor {}
+impl_descriptor!(MuxCodeDescriptor, 33);
 
 
 bit_struct!(
@@ -538,13 +548,13 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 34 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         empty: { value: () },
         // TODO: Missing descriptor fields
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for FmxBufferSizeDescriptor {}
+impl_descriptor!(FmxBufferSizeDescriptor, 34);
 
 
 bit_struct!(
@@ -555,10 +565,10 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 35 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         mb_buffer_size: { 24 },
         tb_leak_rate: { 24 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for MultiplexBufferDescriptor {}
+impl_descriptor!(MultiplexBufferDescriptor, 35);
@@ -12,8 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ::base::{Deserialize,bool_flag};
-use super::{Descriptor,bits_remaining,repeated_element,repeated_sub_element,read_tla};
+use ::base::{Deserialize,Serialize,SerializationError,SerializationResult,bool_flag,decode_bcd};
+use bitwriter::BitWriter;
+use ::time::{DvbDateTime,DvbDuration,TimeResult};
+use super::{bits_remaining,repeated_element,repeated_sub_element,read_tla,tla_bytes};
 use encoding::all::{ISO_8859_1,ISO_8859_2,ISO_8859_3,ISO_8859_4,ISO_8859_5,ISO_8859_6,ISO_8859_7,ISO_8859_8,ISO_8859_10,ISO_8859_13,ISO_8859_14,ISO_8859_15,UTF_16BE,GBK,UTF_8,WINDOWS_949};
 use encoding::{Encoding,DecoderTrap};
 use bitreader;
@@ -27,13 +29,13 @@ bit_struct!(
         pub name: String
     }
     deserialize(reader) {
-        expect: { bits: 8, reference: 0x44 },
-        descriptor_length: { 8 },
-        name: { value: try!(remainder_as_string(descriptor_length, reader)) },
+        expect: { bits: 8, reference: 0x40 },
+        descriptor_length: { 8, length_of: rest },
+        name: { value: try!(remainder_as_string(descriptor_length, reader)), write: string_to_bytes(name) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for NetworkNameDescriptor {}
+impl_descriptor!(NetworkNameDescriptor, 0x40);
 
 
 bit_struct!(
@@ -43,11 +45,8 @@ bit_struct!(
         pub service_type: u8
     }
     deserialize(reader) {
-        expect: { bits: 8, reference: 0x44 },
-        descriptor_length: { 8 },
         service_id: { 16 },
-        service_type: { 8 },
-        skip: { bits_remaining(descriptor_length, reader) }
+        service_type: { 8 }
     }
 );
 
@@ -59,12 +58,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x41 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         services: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ServiceListDescriptor {}
+impl_descriptor!(ServiceListDescriptor, 0x41);
 
 
 // 0x42 StuffingDescriptor
@@ -76,14 +75,81 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x42 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         void: { value: () },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for StuffingDescriptor {}
+impl_descriptor!(StuffingDescriptor, 0x42);
 
 
+// Satellite frequencies are 8 BCD digits in 10 kHz units, cable frequencies 8 digits in 100 Hz
+// units and symbol rates 7 digits in 100 symbols/s units
+const SATELLITE_FREQUENCY_UNIT: u64 = 10_000;
+const CABLE_FREQUENCY_UNIT: u64 = 100;
+const SYMBOL_RATE_UNIT: u64 = 100;
+// Terrestrial frequencies are binary, in 10 Hz units
+const TERRESTRIAL_FREQUENCY_UNIT: u64 = 10;
+
+fn decode_bcd_units(value: u32, digits: u8, unit: u64) -> Option<u64> {
+    decode_bcd(value as u64, digits).map(|value| value * unit)
+}
+
+coded_enum!(
+    pub enum Polarization {
+        0 => LinearHorizontal: "linear - horizontal",
+        1 => LinearVertical: "linear - vertical",
+        2 => CircularLeft: "circular - left",
+        3 => CircularRight: "circular - right"
+    }
+    ranges {
+        _ => Reserved: "reserved"
+    }
+);
+
+coded_enum!(
+    pub enum RollOff {
+        0 => Alpha035: "α = 0,35",
+        1 => Alpha025: "α = 0,25",
+        2 => Alpha020: "α = 0,20"
+    }
+    ranges {
+        _ => Reserved: "reserved"
+    }
+);
+
+coded_enum!(
+    pub enum SatelliteModulationType {
+        0 => Auto: "Auto",
+        1 => Qpsk: "QPSK",
+        2 => Psk8: "8PSK",
+        3 => Qam16: "16-QAM"
+    }
+    ranges {
+        _ => Reserved: "reserved"
+    }
+);
+
+// Inner FEC scheme of satellite and cable delivery systems
+coded_enum!(
+    pub enum FecInner {
+        0 => NotDefined: "not defined",
+        1 => Rate1_2: "1/2 conv. code rate",
+        2 => Rate2_3: "2/3 conv. code rate",
+        3 => Rate3_4: "3/4 conv. code rate",
+        4 => Rate5_6: "5/6 conv. code rate",
+        5 => Rate7_8: "7/8 conv. code rate",
+        6 => Rate8_9: "8/9 conv. code rate",
+        7 => Rate3_5: "3/5 conv. code rate",
+        8 => Rate4_5: "4/5 conv. code rate",
+        9 => Rate9_10: "9/10 conv. code rate",
+        15 => NoConvolutionalCoding: "no conv. coding"
+    }
+    ranges {
+        _ => Reserved: "reserved"
+    }
+);
+
 // 0x43 SatelliteDeliverySystemDescriptor
 bit_struct!(
     #[derive(Debug)]
@@ -100,22 +166,82 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x43 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         frequency: { 32 },
         orbital_position: { 16 },
         west_east: { 1, map: bool_flag },
         polarization: { 2 },
-        roll_off_tmp: { 2 },
+        roll_off_tmp: { 2, write: *roll_off },
         modulation_system: { 1, map: bool_flag },
-        roll_off: { value: if modulation_system { roll_off_tmp } else { 0 } },
+        roll_off: { value: if modulation_system { roll_off_tmp } else { 0 }, write: () },
         modulation_type: { 2 },
         symbol_rate: { 28 },
         fec_inner: { 4 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for SatelliteDeliverySystemDescriptor {}
+impl_descriptor!(SatelliteDeliverySystemDescriptor, 0x43);
 
+impl SatelliteDeliverySystemDescriptor {
+    /// Frequency in Hz, or `None` if the frequency is not valid BCD.
+    pub fn frequency_hz(&self) -> Option<u64> {
+        decode_bcd_units(self.frequency, 8, SATELLITE_FREQUENCY_UNIT)
+    }
+
+    /// Orbital position in tenths of a degree, positive to the east and negative to the west.
+    pub fn orbital_position_tenths(&self) -> Option<i32> {
+        decode_bcd(self.orbital_position as u64, 4).map(|position| {
+            if self.west_east { position as i32 } else { -(position as i32) }
+        })
+    }
+
+    pub fn symbol_rate_symbols_per_second(&self) -> Option<u64> {
+        decode_bcd_units(self.symbol_rate, 7, SYMBOL_RATE_UNIT)
+    }
+
+    pub fn polarization(&self) -> Polarization {
+        Polarization::from(self.polarization)
+    }
+
+    /// Roll-off factor, only signalled for DVB-S2.
+    pub fn roll_off(&self) -> Option<RollOff> {
+        if self.modulation_system { Some(RollOff::from(self.roll_off)) } else { None }
+    }
+
+    pub fn modulation_type(&self) -> SatelliteModulationType {
+        SatelliteModulationType::from(self.modulation_type)
+    }
+
+    pub fn fec_inner(&self) -> FecInner {
+        FecInner::from(self.fec_inner)
+    }
+}
+
+
+coded_enum!(
+    pub enum FecOuter {
+        0 => NotDefined: "not defined",
+        1 => NoOuterFec: "no outer FEC coding",
+        2 => ReedSolomon: "RS(204/188)"
+    }
+    ranges {
+        _ => Reserved: "reserved"
+    }
+);
+
+coded_enum!(
+    pub enum CableModulation {
+        0 => NotDefined: "not defined",
+        1 => Qam16: "16-QAM",
+        2 => Qam32: "32-QAM",
+        3 => Qam64: "64-QAM",
+        4 => Qam128: "128-QAM",
+        5 => Qam256: "256-QAM"
+    }
+    ranges {
+        _ => Reserved: "reserved"
+    }
+);
 
 // 0x44 CableDeliverySystemDescriptor
 bit_struct!(
@@ -129,7 +255,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x44 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         frequency: { 32 },
         reserved: { 12 },
         fec_outer: { 4 },
@@ -139,7 +265,30 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for CableDeliverySystemDescriptor {}
+impl_descriptor!(CableDeliverySystemDescriptor, 0x44);
+
+impl CableDeliverySystemDescriptor {
+    /// Frequency in Hz, or `None` if the frequency is not valid BCD.
+    pub fn frequency_hz(&self) -> Option<u64> {
+        decode_bcd_units(self.frequency, 8, CABLE_FREQUENCY_UNIT)
+    }
+
+    pub fn symbol_rate_symbols_per_second(&self) -> Option<u64> {
+        decode_bcd_units(self.symbol_rate, 7, SYMBOL_RATE_UNIT)
+    }
+
+    pub fn fec_outer(&self) -> FecOuter {
+        FecOuter::from(self.fec_outer)
+    }
+
+    pub fn modulation(&self) -> CableModulation {
+        CableModulation::from(self.modulation)
+    }
+
+    pub fn fec_inner(&self) -> FecInner {
+        FecInner::from(self.fec_inner)
+    }
+}
 
 
 bit_struct!(
@@ -162,17 +311,23 @@ bit_struct!(
         pub vbi_data_lines: Vec<VbiDataLine>
     }
     deserialize(reader) {
-        expect: { bits: 8, reference: 0x44 },
-        descriptor_length: { 8 },
         data_service_id: { 8 },
-        data_service_descriptor_length: { 8 },
-        vbi_data_lines: { value: if data_service_id >= 1 && data_service_id <= 7 {
+        data_service_descriptor_length: { 8, type: u8, length_of: vbi_data_lines },
+        vbi_data_lines: { value: if has_vbi_data_lines(data_service_id) {
             try!(repeated_sub_element(data_service_descriptor_length, reader))
         } else { vec![] } },
-        skip: { bits_remaining(descriptor_length, reader) }
+        // Other data services have reserved bytes instead of lines
+        skip: { if has_vbi_data_lines(data_service_id) { 0 } else { data_service_descriptor_length as u64 * 8 } }
     }
 );
 
+fn has_vbi_data_lines(data_service_id: u8) -> bool {
+    match data_service_id {
+        1 | 2 | 4...7 => true,
+        _ => false,
+    }
+}
+
 // 0x45 VbiDataDescriptor
 bit_struct!(
     #[derive(Debug)]
@@ -181,12 +336,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x45 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         vbi_services: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for VbiDataDescriptor {}
+impl_descriptor!(VbiDataDescriptor, 0x45);
 
 
 // 0x46 VbiTeletextDescriptor
@@ -197,12 +352,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x46 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         vbi_teletext_pages: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for VbiTeletextDescriptor {}
+impl_descriptor!(VbiTeletextDescriptor, 0x46);
 
 
 // 0x47 BouquetNameDescriptor
@@ -213,13 +368,48 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x47 },
-        descriptor_length: { 8 },
-        name: { value: try!(remainder_as_string(descriptor_length, reader)) },
-        skip: { bits_remaining(descriptor_length, reader) }
+        descriptor_length: { 8, length_of: rest },
+        name: { value: try!(remainder_as_string(descriptor_length, reader)), write: string_to_bytes(name) },
+        skip: { bits_remaining(descriptor_length, reader) }
+    }
+);
+impl_descriptor!(BouquetNameDescriptor, 0x47);
+
+
+coded_enum!(
+    pub enum ServiceType {
+        0x01 => DigitalTelevision: "digital television service",
+        0x02 => DigitalRadioSound: "digital radio sound service",
+        0x03 => Teletext: "Teletext service",
+        0x04 => NvodReference: "NVOD reference service",
+        0x05 => NvodTimeShifted: "NVOD time-shifted service",
+        0x06 => Mosaic: "mosaic service",
+        0x07 => FmRadio: "FM radio service",
+        0x08 => DvbSrm: "DVB SRM service",
+        0x0a => AdvancedCodecDigitalRadioSound: "advanced codec digital radio sound service",
+        0x0b => H264Mosaic: "H.264/AVC mosaic service",
+        0x0c => DataBroadcast: "data broadcast service",
+        0x0d => CommonInterface: "reserved for Common Interface Usage",
+        0x0e => RcsMap: "RCS Map",
+        0x0f => RcsFls: "RCS FLS",
+        0x10 => DvbMhp: "DVB MHP service",
+        0x11 => Mpeg2HdDigitalTelevision: "MPEG-2 HD digital television service",
+        0x16 => H264SdDigitalTelevision: "H.264/AVC SD digital television service",
+        0x17 => H264SdNvodTimeShifted: "H.264/AVC SD NVOD time-shifted service",
+        0x18 => H264SdNvodReference: "H.264/AVC SD NVOD reference service",
+        0x19 => H264HdDigitalTelevision: "H.264/AVC HD digital television service",
+        0x1a => H264HdNvodTimeShifted: "H.264/AVC HD NVOD time-shifted service",
+        0x1b => H264HdNvodReference: "H.264/AVC HD NVOD reference service",
+        0x1c => H264StereoscopicHdDigitalTelevision: "H.264/AVC frame compatible plano-stereoscopic HD digital television service",
+        0x1d => H264StereoscopicHdNvodTimeShifted: "H.264/AVC frame compatible plano-stereoscopic HD NVOD time-shifted service",
+        0x1e => H264StereoscopicHdNvodReference: "H.264/AVC frame compatible plano-stereoscopic HD NVOD reference service",
+        0x1f => HevcDigitalTelevision: "HEVC digital television service"
+    }
+    ranges {
+        0x80...0xfe => UserDefined: "user defined",
+        _ => Reserved: "reserved for future use"
     }
 );
-impl Descriptor for BouquetNameDescriptor {}
-
 
 // 0x48 ServiceDescriptor
 bit_struct!(
@@ -231,16 +421,22 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x48 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         service_type: { 8 },
-        service_provider_name_length: { 8 },
-        service_provider_name: { value: try!(read_string(service_provider_name_length, reader)) },
-        service_name_length: { 8 },
-        service_name: { value: try!(read_string(service_name_length, reader)) },
+        service_provider_name_length: { 8, length_of: string_to_bytes(service_provider_name) },
+        service_provider_name: { value: try!(read_string(service_provider_name_length, reader)), write: string_to_bytes(service_provider_name) },
+        service_name_length: { 8, length_of: string_to_bytes(service_name) },
+        service_name: { value: try!(read_string(service_name_length, reader)), write: string_to_bytes(service_name) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ServiceDescriptor {}
+impl_descriptor!(ServiceDescriptor, 0x48);
+
+impl ServiceDescriptor {
+    pub fn service_type(&self) -> ServiceType {
+        ServiceType::from(self.service_type)
+    }
+}
 
 
 // 0x49 CountryAvailabilityDescriptor
@@ -252,7 +448,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x49 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         country_availability: { 1, map: bool_flag },
         reserved: { 7 },
         country_codes: { value: {
@@ -261,11 +457,17 @@ bit_struct!(
                 codes.push(try!(read_tla(reader)));
             }
             codes
+        }, write: {
+            let mut bytes = vec![];
+            for code in country_codes {
+                bytes.extend(try!(tla_bytes(code)));
+            }
+            bytes
         } },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for CountryAvailabilityDescriptor {}
+impl_descriptor!(CountryAvailabilityDescriptor, 0x49);
 
 
 bit_struct!(
@@ -304,6 +506,29 @@ bit_struct!(
     }
 );
 
+coded_enum!(
+    pub enum LinkageType {
+        0x01 => InformationService: "information service",
+        0x02 => EpgService: "EPG service",
+        0x03 => CaReplacementService: "CA replacement service",
+        0x04 => CompleteNetworkBouquetSi: "TS containing complete Network/Bouquet SI",
+        0x05 => ServiceReplacementService: "service replacement service",
+        0x06 => DataBroadcastService: "data broadcast service",
+        0x07 => RcsMap: "RCS Map",
+        0x08 => MobileHandOver: "mobile hand-over",
+        0x09 => SystemSoftwareUpdateService: "System Software Update Service",
+        0x0a => SsuBatOrNit: "TS containing SSU BAT or NIT",
+        0x0b => IpMacNotificationService: "IP/MAC Notification Service",
+        0x0c => IntBatOrNit: "TS containing INT BAT or NIT",
+        0x0d => EventLinkage: "event linkage"
+    }
+    ranges {
+        0x0e...0x1f => ExtendedEventLinkage: "extended event linkage",
+        0x80...0xfe => UserDefined: "user defined",
+        _ => Reserved: "reserved for future use"
+    }
+);
+
 // 0x4a LinkageDescriptor
 bit_struct!(
     #[derive(Debug)]
@@ -318,7 +543,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x4a },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         transport_stream_id: { 16 },
         original_network_id: { 16 },
         service_id: { 16 },
@@ -333,7 +558,13 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for LinkageDescriptor {}
+impl_descriptor!(LinkageDescriptor, 0x4a);
+
+impl LinkageDescriptor {
+    pub fn linkage_type(&self) -> LinkageType {
+        LinkageType::from(self.linkage_type)
+    }
+}
 
 
 bit_struct!(
@@ -358,12 +589,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x4b },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         nvod_references: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for NvodReferenceDescriptor {}
+impl_descriptor!(NvodReferenceDescriptor, 0x4b);
 
 
 // 0x4c TimeShiftedServiceDescriptor
@@ -374,12 +605,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x4c },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         reference_service_id: { 16 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for TimeShiftedServiceDescriptor {}
+impl_descriptor!(TimeShiftedServiceDescriptor, 0x4c);
 
 
 // 0x4d ShortEventDescriptor
@@ -392,16 +623,16 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x4d },
-        descriptor_length: { 8 },
-        iso_639_language_code: { value: try!(read_tla(reader)) },
-        event_name_length: { 8 },
-        event_name: { value: try!(read_string(event_name_length, reader)) },
-        text_length: { 8 },
-        text: { value: try!(read_string(text_length, reader)) },
+        descriptor_length: { 8, length_of: rest },
+        iso_639_language_code: { value: try!(read_tla(reader)), write: try!(tla_bytes(iso_639_language_code)) },
+        event_name_length: { 8, length_of: string_to_bytes(event_name) },
+        event_name: { value: try!(read_string(event_name_length, reader)), write: string_to_bytes(event_name) },
+        text_length: { 8, length_of: string_to_bytes(text) },
+        text: { value: try!(read_string(text_length, reader)), write: string_to_bytes(text) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ShortEventDescriptor {}
+impl_descriptor!(ShortEventDescriptor, 0x4d);
 
 
 bit_struct!(
@@ -411,16 +642,16 @@ bit_struct!(
         pub item_text: String
     }
     deserialize(reader) {
-        item_description_length: { 8, type: u8 },
+        item_description_length: { 8, type: u8, length_of: string_to_bytes(item_description) },
         item_description: { value: {
             let bytes = try!(repeated_sub_element(item_description_length, reader));
             bytes_to_string(&bytes[..])
-        } },
-        item_length: { 8, type: u8 },
+        }, write: string_to_bytes(item_description) },
+        item_length: { 8, type: u8, length_of: string_to_bytes(item_text) },
         item_text: { value: {
             let bytes = try!(repeated_sub_element(item_length, reader));
             bytes_to_string(&bytes[..])
-        } }
+        }, write: string_to_bytes(item_text) }
     }
 );
 
@@ -436,17 +667,17 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x4e },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         descriptor_number: { 4 },
         last_descriptor_number: { 4 },
-        iso_639_language_code: { value: try!(read_tla(reader)) },
-        length_of_items: { 8 },
+        iso_639_language_code: { value: try!(read_tla(reader)), write: try!(tla_bytes(iso_639_language_code)) },
+        length_of_items: { 8, length_of: items },
         items: { value: try!(repeated_sub_element(length_of_items, reader)) },
-        text: { value: try!(remainder_as_string(descriptor_length, reader)) },
+        text: { value: try!(remainder_as_string(descriptor_length, reader)), write: string_to_bytes(text) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ExtendedEventDescriptor {}
+impl_descriptor!(ExtendedEventDescriptor, 0x4e);
 
 
 // 0x4f TimeShiftedEventDescriptor
@@ -458,14 +689,143 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x4f },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         reference_service_id: { 16 },
         reference_event_id: { 16 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for TimeShiftedEventDescriptor {}
+impl_descriptor!(TimeShiftedEventDescriptor, 0x4f);
+
+
+coded_enum!(
+    pub enum StreamContent {
+        0x01 => Mpeg2Video: "MPEG-2 video",
+        0x02 => Mpeg1Layer2Audio: "MPEG-1 Layer 2 audio",
+        0x03 => Subtitling: "EBU Teletext, VBI data or DVB subtitles",
+        0x04 => Ac3Audio: "AC-3 audio",
+        0x05 => H264Video: "H.264/AVC video",
+        0x06 => HeAacAudio: "HE-AAC audio",
+        0x07 => DtsAudio: "DTS audio",
+        0x08 => DvbSrmData: "DVB SRM data"
+    }
+    ranges {
+        0x0c...0x0f => UserDefined: "user defined",
+        _ => Reserved: "reserved for future use"
+    }
+);
+
+/// Meaning of a component_type, which depends on stream_content.
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct ComponentType {
+    pub stream_content: StreamContent,
+    pub component_type: u8,
+}
+
+// EN 300 468 Table 26. The component_type of AC-3 and DTS audio is a bit field defined in Annex D
+// and Annex G, and is not listed.
+const COMPONENT_TYPES: &'static [(u8, u8, &'static str)] = &[
+    (0x01, 0x01, "MPEG-2 video, 4:3 aspect ratio, 25 Hz"),
+    (0x01, 0x02, "MPEG-2 video, 16:9 aspect ratio with pan vectors, 25 Hz"),
+    (0x01, 0x03, "MPEG-2 video, 16:9 aspect ratio without pan vectors, 25 Hz"),
+    (0x01, 0x04, "MPEG-2 video, > 16:9 aspect ratio, 25 Hz"),
+    (0x01, 0x05, "MPEG-2 video, 4:3 aspect ratio, 30 Hz"),
+    (0x01, 0x06, "MPEG-2 video, 16:9 aspect ratio with pan vectors, 30 Hz"),
+    (0x01, 0x07, "MPEG-2 video, 16:9 aspect ratio without pan vectors, 30 Hz"),
+    (0x01, 0x08, "MPEG-2 video, > 16:9 aspect ratio, 30 Hz"),
+    (0x01, 0x09, "MPEG-2 high definition video, 4:3 aspect ratio, 25 Hz"),
+    (0x01, 0x0a, "MPEG-2 high definition video, 16:9 aspect ratio with pan vectors, 25 Hz"),
+    (0x01, 0x0b, "MPEG-2 high definition video, 16:9 aspect ratio without pan vectors, 25 Hz"),
+    (0x01, 0x0c, "MPEG-2 high definition video, > 16:9 aspect ratio, 25 Hz"),
+    (0x01, 0x0d, "MPEG-2 high definition video, 4:3 aspect ratio, 30 Hz"),
+    (0x01, 0x0e, "MPEG-2 high definition video, 16:9 aspect ratio with pan vectors, 30 Hz"),
+    (0x01, 0x0f, "MPEG-2 high definition video, 16:9 aspect ratio without pan vectors, 30 Hz"),
+    (0x01, 0x10, "MPEG-2 high definition video, > 16:9 aspect ratio, 30 Hz"),
+    (0x02, 0x01, "MPEG-1 Layer 2 audio, single mono channel"),
+    (0x02, 0x02, "MPEG-1 Layer 2 audio, dual mono channel"),
+    (0x02, 0x03, "MPEG-1 Layer 2 audio, stereo (2 channel)"),
+    (0x02, 0x04, "MPEG-1 Layer 2 audio, multi-lingual, multi-channel"),
+    (0x02, 0x05, "MPEG-1 Layer 2 audio, surround sound"),
+    (0x02, 0x40, "MPEG-1 Layer 2 audio description for the visually impaired"),
+    (0x02, 0x41, "MPEG-1 Layer 2 audio for the hard of hearing"),
+    (0x02, 0x42, "receiver-mix supplementary audio"),
+    (0x02, 0x47, "MPEG-1 Layer 2 audio, receiver-mix audio description"),
+    (0x02, 0x48, "MPEG-1 Layer 2 audio, broadcast-mix audio description"),
+    (0x03, 0x01, "EBU Teletext subtitles"),
+    (0x03, 0x02, "associated EBU Teletext"),
+    (0x03, 0x03, "VBI data"),
+    (0x03, 0x10, "DVB subtitles (normal) with no monitor aspect ratio criticality"),
+    (0x03, 0x11, "DVB subtitles (normal) for display on 4:3 aspect ratio monitor"),
+    (0x03, 0x12, "DVB subtitles (normal) for display on 16:9 aspect ratio monitor"),
+    (0x03, 0x13, "DVB subtitles (normal) for display on 2.21:1 aspect ratio monitor"),
+    (0x03, 0x14, "DVB subtitles (normal) for display on a high definition monitor"),
+    (0x03, 0x20, "DVB subtitles (for the hard of hearing) with no monitor aspect ratio criticality"),
+    (0x03, 0x21, "DVB subtitles (for the hard of hearing) for display on 4:3 aspect ratio monitor"),
+    (0x03, 0x22, "DVB subtitles (for the hard of hearing) for display on 16:9 aspect ratio monitor"),
+    (0x03, 0x23, "DVB subtitles (for the hard of hearing) for display on 2.21:1 aspect ratio monitor"),
+    (0x03, 0x24, "DVB subtitles (for the hard of hearing) for display on a high definition monitor"),
+    (0x03, 0x30, "open (in-vision) sign language interpretation for the deaf"),
+    (0x03, 0x31, "closed sign language interpretation for the deaf"),
+    (0x03, 0x40, "video up-sampled from standard definition source material"),
+    (0x05, 0x01, "H.264/AVC standard definition video, 4:3 aspect ratio, 25 Hz"),
+    (0x05, 0x03, "H.264/AVC standard definition video, 16:9 aspect ratio, 25 Hz"),
+    (0x05, 0x04, "H.264/AVC standard definition video, > 16:9 aspect ratio, 25 Hz"),
+    (0x05, 0x05, "H.264/AVC standard definition video, 4:3 aspect ratio, 30 Hz"),
+    (0x05, 0x07, "H.264/AVC standard definition video, 16:9 aspect ratio, 30 Hz"),
+    (0x05, 0x08, "H.264/AVC standard definition video, > 16:9 aspect ratio, 30 Hz"),
+    (0x05, 0x0b, "H.264/AVC high definition video, 16:9 aspect ratio, 25 Hz"),
+    (0x05, 0x0c, "H.264/AVC high definition video, > 16:9 aspect ratio, 25 Hz"),
+    (0x05, 0x0f, "H.264/AVC high definition video, 16:9 aspect ratio, 30 Hz"),
+    (0x05, 0x10, "H.264/AVC high definition video, > 16:9 aspect ratio, 30 Hz"),
+    (0x05, 0x80, "H.264/AVC plano-stereoscopic frame compatible high definition video, 16:9 aspect ratio, 25 Hz, Side-by-Side"),
+    (0x05, 0x81, "H.264/AVC plano-stereoscopic frame compatible high definition video, 16:9 aspect ratio, 25 Hz, Top-and-Bottom"),
+    (0x05, 0x82, "H.264/AVC plano-stereoscopic frame compatible high definition video, 16:9 aspect ratio, 30 Hz, Side-by-Side"),
+    (0x05, 0x83, "H.264/AVC plano-stereoscopic frame compatible high definition video, 16:9 aspect ratio, 30 Hz, Top-and-Bottom"),
+    (0x05, 0x84, "H.264/MVC dependent view, plano-stereoscopic service compatible video"),
+    (0x06, 0x01, "HE-AAC audio, single mono channel"),
+    (0x06, 0x03, "HE-AAC audio, stereo"),
+    (0x06, 0x05, "HE-AAC audio, surround sound"),
+    (0x06, 0x40, "HE-AAC audio description for the visually impaired"),
+    (0x06, 0x41, "HE-AAC audio for the hard of hearing"),
+    (0x06, 0x42, "HE-AAC receiver-mix supplementary audio"),
+    (0x06, 0x43, "HE-AAC v2 audio, stereo"),
+    (0x06, 0x44, "HE-AAC v2 audio description for the visually impaired"),
+    (0x06, 0x45, "HE-AAC v2 audio for the hard of hearing"),
+    (0x06, 0x46, "HE-AAC v2 receiver-mix supplementary audio"),
+    (0x06, 0x47, "HE-AAC receiver-mix audio description for the visually impaired"),
+    (0x06, 0x48, "HE-AAC broadcast-mix audio description for the visually impaired"),
+    (0x06, 0x49, "HE-AAC v2 receiver-mix audio description for the visually impaired"),
+    (0x06, 0x4a, "HE-AAC v2 broadcast-mix audio description for the visually impaired"),
+    (0x08, 0x01, "DVB SRM data"),
+];
+
+impl ComponentType {
+    /// Description of the component from EN 300 468, `None` for reserved and user defined values
+    /// and for bit field coded AC-3 and DTS audio.
+    pub fn description(&self) -> Option<&'static str> {
+        let stream_content = u8::from(self.stream_content);
+        COMPONENT_TYPES.iter()
+            .find(|&&(content, component_type, _)| content == stream_content && component_type == self.component_type)
+            .map(|&(_, _, description)| description)
+    }
+
+    pub fn is_user_defined(&self) -> bool {
+        match self.stream_content {
+            StreamContent::Reserved(_) => false,
+            _ => self.component_type >= 0xb0 && self.component_type <= 0xfe,
+        }
+    }
+}
 
+impl ::std::fmt::Display for ComponentType {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match self.description() {
+            Some(description) => write!(f, "{}", description),
+            None if self.is_user_defined() => write!(f, "{}, user defined (0x{:02x})", self.stream_content, self.component_type),
+            None => write!(f, "{}, component_type 0x{:02x}", self.stream_content, self.component_type),
+        }
+    }
+}
 
 // 0x50 ComponentDescriptor
 bit_struct!(
@@ -479,17 +839,30 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x50 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         reserved: { 4 },
         stream_content: { 4 },
         component_type: { 8 },
         component_tag: { 8 },
-        iso_639_language_code: { value: try!(read_tla(reader)) },
-        description: { value: try!(remainder_as_string(descriptor_length, reader)) },
+        iso_639_language_code: { value: try!(read_tla(reader)), write: try!(tla_bytes(iso_639_language_code)) },
+        description: { value: try!(remainder_as_string(descriptor_length, reader)), write: string_to_bytes(description) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ComponentDescriptor {}
+impl_descriptor!(ComponentDescriptor, 0x50);
+
+impl ComponentDescriptor {
+    pub fn stream_content(&self) -> StreamContent {
+        StreamContent::from(self.stream_content)
+    }
+
+    pub fn component_type(&self) -> ComponentType {
+        ComponentType {
+            stream_content: self.stream_content(),
+            component_type: self.component_type,
+        }
+    }
+}
 
 
 #[derive(Debug)]
@@ -544,6 +917,32 @@ fn read_mosaic_cell_linkage(cell_linkage_info: u8, reader: &mut bitreader::BitRe
     Ok(linkage)
 }
 
+fn mosaic_cell_linkage_info(linkage: &MosaicCellLinkage) -> u8 {
+    match *linkage {
+        MosaicCellLinkage::Undefined => 0,
+        MosaicCellLinkage::BouquetRelated { .. } => 1,
+        MosaicCellLinkage::ServiceRelated { .. } => 2,
+        MosaicCellLinkage::OtherMosaicRelated { .. } => 3,
+        MosaicCellLinkage::EventRelated { .. } => 4,
+        MosaicCellLinkage::Unrecognized(cell_linkage_info) => cell_linkage_info,
+    }
+}
+
+impl Serialize for MosaicCellLinkage {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()> {
+        let ids = match *self {
+            MosaicCellLinkage::Undefined | MosaicCellLinkage::Unrecognized(..) => vec![],
+            MosaicCellLinkage::BouquetRelated { bouquet_id } => vec![bouquet_id],
+            MosaicCellLinkage::ServiceRelated { original_network_id, transport_stream_id, service_id } |
+            MosaicCellLinkage::OtherMosaicRelated { original_network_id, transport_stream_id, service_id } =>
+                vec![original_network_id, transport_stream_id, service_id],
+            MosaicCellLinkage::EventRelated { original_network_id, transport_stream_id, service_id, event_id } =>
+                vec![original_network_id, transport_stream_id, service_id, event_id],
+        };
+        ids.serialize(writer)
+    }
+}
+
 bit_struct!(
     #[derive(Debug)]
     pub struct MosaicElementaryCell {
@@ -553,10 +952,10 @@ bit_struct!(
         pub cell_linkage: MosaicCellLinkage
     }
     deserialize(reader) {
-        logical_cell_id: { 8 },
-        reserved: { 1 },
+        logical_cell_id: { 6 },
+        reserved: { 7 },
         logical_cell_presentation_info: { 3 },
-        elementary_cell_lenght_field: { 8 },
+        elementary_cell_lenght_field: { 8, length_of: elementary_cell_ids },
         elementary_cell_ids: { value: {
             let mut ids = vec![];
             for _ in 0..elementary_cell_lenght_field {
@@ -564,8 +963,8 @@ bit_struct!(
                 ids.push(try!(reader.read_u8(6)));
             }
             ids
-        } },
-        cell_linkage_info: { 8 },
+        }, write: elementary_cell_ids.iter().map(|id| 0xc0 | id).collect::<Vec<u8>>() },
+        cell_linkage_info: { 8, write: mosaic_cell_linkage_info(cell_linkage) },
         cell_linkage: { value: try!(read_mosaic_cell_linkage(cell_linkage_info, reader)) }
     }
 );
@@ -581,7 +980,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x51 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         mosaic_entry_point: { 1, map: bool_flag },
         number_of_horizontal_elementary_cells: { 3 },
         reserved: { 1 },
@@ -590,7 +989,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for MosaicDescriptor {}
+impl_descriptor!(MosaicDescriptor, 0x51);
 
 
 // 0x52 StreamIdentifierDescriptor
@@ -601,12 +1000,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x52 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         component_tag: { 8 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for StreamIdentifierDescriptor {}
+impl_descriptor!(StreamIdentifierDescriptor, 0x52);
 
 // 0x53 CaIdentifierDescriptor
 bit_struct!(
@@ -616,7 +1015,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x53 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         ca_system_ids: { value: {
             let mut ids = vec![];
             while bits_remaining(descriptor_length, reader) >= 16 {
@@ -627,7 +1026,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for CaIdentifierDescriptor {}
+impl_descriptor!(CaIdentifierDescriptor, 0x53);
 
 bit_struct!(
     #[derive(Debug)]
@@ -652,12 +1051,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x54 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         content_idenfiers: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ContentDescriptor {}
+impl_descriptor!(ContentDescriptor, 0x54);
 
 
 bit_struct!(
@@ -667,11 +1066,8 @@ bit_struct!(
         pub rating: u8
     }
     deserialize(reader) {
-        expect: { bits: 8, reference: 0x54 },
-        descriptor_length: { 8 },
-        country_code: { value: try!(read_tla(reader)) },
-        rating: { 8 },
-        skip: { bits_remaining(descriptor_length, reader) }
+        country_code: { value: try!(read_tla(reader)), write: try!(tla_bytes(country_code)) },
+        rating: { 8 }
     }
 );
 
@@ -683,12 +1079,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x55 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         ratings: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ParentalRatingDescriptor {}
+impl_descriptor!(ParentalRatingDescriptor, 0x55);
 
 
 bit_struct!(
@@ -700,7 +1096,7 @@ bit_struct!(
         pub teletext_page_number: u8
     }
     deserialize(reader) {
-        iso_639_language_code: { value: try!(read_tla(reader)) },
+        iso_639_language_code: { value: try!(read_tla(reader)), write: try!(tla_bytes(iso_639_language_code)) },
         teletext_type: { 5 },
         teletext_magazine_number: { 3 },
         teletext_page_number: { 8 }
@@ -715,12 +1111,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x56 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         teletext_pages: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for TeletextDescriptor {}
+impl_descriptor!(TeletextDescriptor, 0x56);
 
 
 // 0x57 TelephoneDescriptor
@@ -737,26 +1133,26 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x57 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         reserved: { 2 },
         foreign_availability: { 1, map: bool_flag },
         connection_type: { 5 },
         reserved: { 1 },
-        country_prefix_length: { 2 },
-        international_area_code_length: { 3 },
-        operator_code_length: { 2 },
+        country_prefix_length: { 2, length_of: try!(string_to_bytes_latin1(country_prefix)) },
+        international_area_code_length: { 3, length_of: try!(string_to_bytes_latin1(international_area_code)) },
+        operator_code_length: { 2, length_of: try!(string_to_bytes_latin1(operator_code)) },
         reserved: { 1 },
-        national_area_code_length: { 3 },
-        core_number_length: { 4 },
-        country_prefix: { value: try!(read_string_latin1(country_prefix_length, reader)) },
-        international_area_code: { value: try!(read_string_latin1(international_area_code_length, reader)) },
-        operator_code: { value: try!(read_string_latin1(operator_code_length, reader)) },
-        national_area_code: { value: try!(read_string_latin1(national_area_code_length, reader)) },
-        core_number: { value: try!(read_string_latin1(core_number_length, reader)) },
+        national_area_code_length: { 3, length_of: try!(string_to_bytes_latin1(national_area_code)) },
+        core_number_length: { 4, length_of: try!(string_to_bytes_latin1(core_number)) },
+        country_prefix: { value: try!(read_string_latin1(country_prefix_length, reader)), write: try!(string_to_bytes_latin1(country_prefix)) },
+        international_area_code: { value: try!(read_string_latin1(international_area_code_length, reader)), write: try!(string_to_bytes_latin1(international_area_code)) },
+        operator_code: { value: try!(read_string_latin1(operator_code_length, reader)), write: try!(string_to_bytes_latin1(operator_code)) },
+        national_area_code: { value: try!(read_string_latin1(national_area_code_length, reader)), write: try!(string_to_bytes_latin1(national_area_code)) },
+        core_number: { value: try!(read_string_latin1(core_number_length, reader)), write: try!(string_to_bytes_latin1(core_number)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for TelephoneDescriptor {}
+impl_descriptor!(TelephoneDescriptor, 0x57);
 
 
 bit_struct!(
@@ -765,21 +1161,38 @@ bit_struct!(
         pub country_code: String,
         pub country_region_id: u8,
         pub local_time_offset_polarity: u8,
-        pub local_time_offset: u16,
-        pub time_of_change: u64,
-        pub next_time_offset: u16
+        pub local_time_offset: DvbDuration,
+        pub time_of_change: DvbDateTime,
+        pub next_time_offset: DvbDuration
     }
     deserialize(reader) {
-        country_code: { value: try!(read_tla(reader)) },
+        country_code: { value: try!(read_tla(reader)), write: try!(tla_bytes(country_code)) },
         country_region_id: { 6 },
         reserved: { 1 },
         local_time_offset_polarity: { 1 },
-        local_time_offset: { 16 },
-        time_of_change: { 40 },
-        next_time_offset: { 16 }
+        local_time_offset: { 16, map: DvbDuration::from_hhmm_bcd, write: local_time_offset.hhmm_bcd() },
+        time_of_change: { value: try!(Deserialize::deserialize(reader)) },
+        next_time_offset: { 16, map: DvbDuration::from_hhmm_bcd, write: next_time_offset.hhmm_bcd() }
     }
 );
 
+impl LocalTimeOffset {
+    /// The current offset from UTC in seconds, negative when the polarity bit is set.
+    pub fn local_time_offset_seconds(&self) -> TimeResult<i32> {
+        self.signed_seconds(&self.local_time_offset)
+    }
+
+    /// The offset from UTC in seconds after time_of_change, negative when the polarity bit is set.
+    pub fn next_time_offset_seconds(&self) -> TimeResult<i32> {
+        self.signed_seconds(&self.next_time_offset)
+    }
+
+    fn signed_seconds(&self, offset: &DvbDuration) -> TimeResult<i32> {
+        let seconds = try!(offset.to_seconds()) as i32;
+        Ok(if self.local_time_offset_polarity == 1 { -seconds } else { seconds })
+    }
+}
+
 // 0x58 LocalTimeOffsetDescriptor
 bit_struct!(
     #[derive(Debug)]
@@ -788,12 +1201,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x58 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         offsets: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for LocalTimeOffsetDescriptor {}
+impl_descriptor!(LocalTimeOffsetDescriptor, 0x58);
 
 
 bit_struct!(
@@ -805,7 +1218,7 @@ bit_struct!(
         pub ancillary_page_id: u16
     }
     deserialize(reader) {
-        iso_639_language_code: { value: try!(read_tla(reader)) },
+        iso_639_language_code: { value: try!(read_tla(reader)), write: try!(tla_bytes(iso_639_language_code)) },
         subtitling_page: { 8 },
         composition_page_id: { 16 },
         ancillary_page_id: { 16 }
@@ -820,13 +1233,59 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x59 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         subtitles: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for SubtitlingDescriptor {}
+impl_descriptor!(SubtitlingDescriptor, 0x59);
+
+
+coded_enum!(
+    pub enum Bandwidth {
+        0 => Mhz8: "8 MHz",
+        1 => Mhz7: "7 MHz",
+        2 => Mhz6: "6 MHz",
+        3 => Mhz5: "5 MHz"
+    }
+    ranges {
+        _ => Reserved: "reserved for future use"
+    }
+);
 
+coded_enum!(
+    pub enum Constellation {
+        0 => Qpsk: "QPSK",
+        1 => Qam16: "16-QAM",
+        2 => Qam64: "64-QAM"
+    }
+    ranges {
+        _ => Reserved: "reserved for future use"
+    }
+);
+
+coded_enum!(
+    pub enum GuardInterval {
+        0 => Interval1_32: "1/32",
+        1 => Interval1_16: "1/16",
+        2 => Interval1_8: "1/8",
+        3 => Interval1_4: "1/4"
+    }
+    ranges {
+        _ => Reserved: "reserved for future use"
+    }
+);
+
+coded_enum!(
+    pub enum TransmissionMode {
+        0 => Mode2k: "2k mode",
+        1 => Mode8k: "8k mode",
+        2 => Mode4k: "4k mode"
+    }
+    ranges {
+        _ => Reserved: "reserved for future use"
+    }
+);
 
 // 0x5a TerrestrialDeliverySystemDescriptor
 bit_struct!(
@@ -847,7 +1306,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x5a },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         centre_frequency: { 32 },
         bandwidth: { 3 },
         priority: { 1, map: bool_flag },
@@ -865,7 +1324,29 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for TerrestrialDeliverySystemDescriptor {}
+impl_descriptor!(TerrestrialDeliverySystemDescriptor, 0x5a);
+
+impl TerrestrialDeliverySystemDescriptor {
+    pub fn centre_frequency_hz(&self) -> u64 {
+        self.centre_frequency as u64 * TERRESTRIAL_FREQUENCY_UNIT
+    }
+
+    pub fn bandwidth(&self) -> Bandwidth {
+        Bandwidth::from(self.bandwidth)
+    }
+
+    pub fn constellation(&self) -> Constellation {
+        Constellation::from(self.constellation)
+    }
+
+    pub fn guard_interval(&self) -> GuardInterval {
+        GuardInterval::from(self.guard_interval)
+    }
+
+    pub fn transmission_mode(&self) -> TransmissionMode {
+        TransmissionMode::from(self.transmission_mode)
+    }
+}
 
 
 
@@ -876,9 +1357,9 @@ bit_struct!(
         pub text: String
     }
     deserialize(reader) {
-        iso_639_language_code: { value: try!(read_tla(reader)) },
-        text_length: { 8 },
-        text: { value: try!(read_string(text_length, reader)) }
+        iso_639_language_code: { value: try!(read_tla(reader)), write: try!(tla_bytes(iso_639_language_code)) },
+        text_length: { 8, length_of: string_to_bytes(text) },
+        text: { value: try!(read_string(text_length, reader)), write: string_to_bytes(text) }
     }
 );
 
@@ -890,12 +1371,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x5b },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         network_names: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for MultilingualNetworkNameDescriptor {}
+impl_descriptor!(MultilingualNetworkNameDescriptor, 0x5b);
 
 
 // 0x5c MultilingualBouquetNameDescriptor
@@ -906,12 +1387,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x5c },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         bouquet_names: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for MultilingualBouquetNameDescriptor {}
+impl_descriptor!(MultilingualBouquetNameDescriptor, 0x5c);
 
 
 bit_struct!(
@@ -922,11 +1403,11 @@ bit_struct!(
         pub service_name: String
     }
     deserialize(reader) {
-        iso_639_language_code: { value: try!(read_tla(reader)) },
-        service_provider_name_length: { 8 },
-        service_provider_name: { value: try!(read_string(service_provider_name_length, reader)) },
-        service_name_length: { 8 },
-        service_name: { value: try!(read_string(service_name_length, reader)) }
+        iso_639_language_code: { value: try!(read_tla(reader)), write: try!(tla_bytes(iso_639_language_code)) },
+        service_provider_name_length: { 8, length_of: string_to_bytes(service_provider_name) },
+        service_provider_name: { value: try!(read_string(service_provider_name_length, reader)), write: string_to_bytes(service_provider_name) },
+        service_name_length: { 8, length_of: string_to_bytes(service_name) },
+        service_name: { value: try!(read_string(service_name_length, reader)), write: string_to_bytes(service_name) }
     }
 );
 
@@ -938,12 +1419,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x5d },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         service_names: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for MultilingualServiceNameDescriptor {}
+impl_descriptor!(MultilingualServiceNameDescriptor, 0x5d);
 
 
 // 0x5e MultilingualComponentDescriptor
@@ -955,13 +1436,13 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x5e },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         component_tag: { 8 },
         text_descriptions: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for MultilingualComponentDescriptor {}
+impl_descriptor!(MultilingualComponentDescriptor, 0x5e);
 
 
 // 0x5f PrivateDataSpecifierDescriptor
@@ -972,12 +1453,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x5f },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         private_data_specifier: { 32 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for PrivateDataSpecifierDescriptor {}
+impl_descriptor!(PrivateDataSpecifierDescriptor, 0x5f);
 
 
 // 0x60 ServiceMoveDescriptor
@@ -990,14 +1471,14 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x60 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         new_original_network_id: { 16 },
         new_transport_stream_id: { 16 },
         new_service_id: { 16 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ServiceMoveDescriptor {}
+impl_descriptor!(ServiceMoveDescriptor, 0x60);
 
 
 // 0x61 ShortSmoothingBufferDescriptor
@@ -1008,16 +1489,28 @@ bit_struct!(
         pub sb_leak_rate: u8
     }
     deserialize(reader) {
-        expect: { bits: 8, reference: 0x60 },
-        descriptor_length: { 8 },
+        expect: { bits: 8, reference: 0x61 },
+        descriptor_length: { 8, length_of: rest },
         sb_size: { 2 },
         sb_leak_rate: { 6 },
         // The spec lists N reserved bytes here explicitly, we will just rely on the default skip here:
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ShortSmoothingBufferDescriptor {}
+impl_descriptor!(ShortSmoothingBufferDescriptor, 0x61);
+
 
+coded_enum!(
+    pub enum FrequencyCodingType {
+        0 => NotDefined: "not defined",
+        1 => Satellite: "satellite",
+        2 => Cable: "cable",
+        3 => Terrestrial: "terrestrial"
+    }
+    ranges {
+        _ => Reserved: "reserved"
+    }
+);
 
 // 0x62 FrequencyListDescriptor
 bit_struct!(
@@ -1028,14 +1521,33 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x62 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         reserved: { 6 },
         coding_type: { 2 },
         centre_frequencies: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for FrequencyListDescriptor {}
+impl_descriptor!(FrequencyListDescriptor, 0x62);
+
+impl FrequencyListDescriptor {
+    pub fn coding_type(&self) -> FrequencyCodingType {
+        FrequencyCodingType::from(self.coding_type)
+    }
+
+    /// Centre frequencies in Hz, coded as in the delivery system descriptor of coding_type.
+    /// `None` if the coding is not defined or a frequency is not valid BCD.
+    pub fn centre_frequencies_hz(&self) -> Option<Vec<u64>> {
+        self.centre_frequencies.iter().map(|&frequency| {
+            match self.coding_type() {
+                FrequencyCodingType::Satellite => decode_bcd_units(frequency, 8, SATELLITE_FREQUENCY_UNIT),
+                FrequencyCodingType::Cable => decode_bcd_units(frequency, 8, CABLE_FREQUENCY_UNIT),
+                FrequencyCodingType::Terrestrial => Some(frequency as u64 * TERRESTRIAL_FREQUENCY_UNIT),
+                _ => None,
+            }
+        }).collect()
+    }
+}
 
 
 // 0x63 PartialTransportStreamDescriptor
@@ -1048,7 +1560,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x63 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         reserved: { 2 },
         peak_rate: { 22 },
         reserved: { 2 },
@@ -1058,7 +1570,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for PartialTransportStreamDescriptor {}
+impl_descriptor!(PartialTransportStreamDescriptor, 0x63);
 
 
 // 0x64 DataBroadcastDescriptor
@@ -1073,17 +1585,17 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x64 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         data_broadcast_id: { 16 },
         component_tag: { 8 },
-        selector_length: { 8 },
+        selector_length: { 8, length_of: selector_bytes },
         selector_bytes: { value: try!(repeated_sub_element(selector_length, reader)) },
-        iso_639_language_code: { value: try!(read_tla(reader)) },
-        text: { value: try!(remainder_as_string(descriptor_length, reader)) },
+        iso_639_language_code: { value: try!(read_tla(reader)), write: try!(tla_bytes(iso_639_language_code)) },
+        text: { value: try!(remainder_as_string(descriptor_length, reader)), write: string_to_bytes(text) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for DataBroadcastDescriptor {}
+impl_descriptor!(DataBroadcastDescriptor, 0x64);
 
 
 // 0x65 ScramblingDescriptor
@@ -1094,12 +1606,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x65 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         scrambling_mode: { 8 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ScramblingDescriptor {}
+impl_descriptor!(ScramblingDescriptor, 0x65);
 
 
 // 0x66 DataBroadcastIdDescriptor
@@ -1111,13 +1623,13 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x66 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         data_broadcast_id: { 16 },
         selector_bytes: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for DataBroadcastIdDescriptor {}
+impl_descriptor!(DataBroadcastIdDescriptor, 0x66);
 
 
 // 0x67 TransportStreamDescriptor
@@ -1128,12 +1640,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x67 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         bytes: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for TransportStreamDescriptor {}
+impl_descriptor!(TransportStreamDescriptor, 0x67);
 
 
 // 0x68 DsngDescriptor
@@ -1144,12 +1656,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x68 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         bytes: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for DsngDescriptor {}
+impl_descriptor!(DsngDescriptor, 0x68);
 
 
 // 0x69 PdcDescriptor
@@ -1160,13 +1672,13 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x69 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         reserved: { 4 },
         programme_identification_label: { 20 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for PdcDescriptor {}
+impl_descriptor!(PdcDescriptor, 0x69);
 
 
 // 0x6a Ac3Descriptor
@@ -1180,11 +1692,11 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x6a },
-        descriptor_length: { 8 },
-        component_type_flag: { 1, type: u8 },
-        bsid_flag: { 1, type: u8 },
-        mainid_flag: { 1, type: u8 },
-        asvc_flag: { 1, type: u8 },
+        descriptor_length: { 8, length_of: rest },
+        component_type_flag: { 1, type: u8, write: component_type.is_some() },
+        bsid_flag: { 1, type: u8, write: bsid.is_some() },
+        mainid_flag: { 1, type: u8, write: mainid.is_some() },
+        asvc_flag: { 1, type: u8, write: asvc.is_some() },
         reserved: { 4 },
         component_type: { value: if component_type_flag == 1 { Some(try!(reader.read_u8(8))) } else { None } },
         bsid: { value: if bsid_flag == 1 { Some(try!(reader.read_u8(8))) } else { None } },
@@ -1193,7 +1705,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for Ac3Descriptor {}
+impl_descriptor!(Ac3Descriptor, 0x6a);
 
 
 // 0x6b AncillaryDataDescriptor
@@ -1204,12 +1716,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x6b },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         data: { 8 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for AncillaryDataDescriptor {}
+impl_descriptor!(AncillaryDataDescriptor, 0x6b);
 
 
 bit_struct!(
@@ -1246,7 +1758,7 @@ bit_struct!(
         cell_longitude: { 16 },
         cell_extent_of_latitude: { 12 },
         cell_extent_of_longitude: { 12 },
-        subcell_info_loop_length: { 8, type: u8 },
+        subcell_info_loop_length: { 8, type: u8, length_of: subcells },
         subcells: { value: try!(repeated_sub_element(subcell_info_loop_length, reader)) }
     }
 );
@@ -1259,12 +1771,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x6c },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         cells: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for CellListDescriptor {}
+impl_descriptor!(CellListDescriptor, 0x6c);
 
 bit_struct!(
     #[derive(Debug)]
@@ -1288,7 +1800,7 @@ bit_struct!(
     deserialize(reader) {
         cell_id: { 16 },
         frequency: { 32 },
-        subcell_info_loop_length: { 8, type: u8 },
+        subcell_info_loop_length: { 8, type: u8, length_of: subcells },
         subcells: { value: try!(repeated_sub_element(subcell_info_loop_length, reader)) }
     }
 );
@@ -1301,21 +1813,29 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x6d },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         cells: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for CellFrequencyLinkDescriptor {}
+impl_descriptor!(CellFrequencyLinkDescriptor, 0x6d);
 
 
-#[derive(Debug)]
-pub struct AnnouncementService {
+bit_struct!(
+    #[derive(Debug)]
+    pub struct AnnouncementService {
         pub original_network_id: u16,
         pub transport_stream_id: u16,
         pub service_id: u16,
-        pub component_tag: u8,
-}
+        pub component_tag: u8
+    }
+    deserialize(reader) {
+        original_network_id: { 16 },
+        transport_stream_id: { 16 },
+        service_id: { 16 },
+        component_tag: { 8 }
+    }
+);
 
 bit_struct!(
     #[derive(Debug)]
@@ -1325,24 +1845,16 @@ bit_struct!(
         pub service: Option<AnnouncementService>
     }
     deserialize(reader) {
-        expect: { bits: 8, reference: 0x6e },
-        descriptor_length: { 8 },
         announcement_type: { 4 },
         reserved: { 1 },
         reference_type: { 3 },
         service: { value: {
             if reference_type == 1 || reference_type == 2 || reference_type == 3 {
-                Some(AnnouncementService {
-                    original_network_id: try!(reader.read_u16(16)),
-                    transport_stream_id: try!(reader.read_u16(16)),
-                    service_id: try!(reader.read_u16(16)),
-                    component_tag: try!(reader.read_u8(8)),
-                })
+                Some(try!(Deserialize::deserialize(reader)))
             } else {
                 None
             }
-        } },
-        skip: { bits_remaining(descriptor_length, reader) }
+        } }
     }
 );
 
@@ -1355,13 +1867,13 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x6e },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         announcement_support: { 16 },
         services: { value: { try!(repeated_element(descriptor_length, reader)) } },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for AnnouncementSupportDescriptor {}
+impl_descriptor!(AnnouncementSupportDescriptor, 0x6e);
 
 
 bit_struct!(
@@ -1386,12 +1898,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x6f },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         application_versions: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ApplicationSignallingDescriptor {}
+impl_descriptor!(ApplicationSignallingDescriptor, 0x6f);
 
 
 // 0x70 AdaptationFieldDataDescriptor
@@ -1402,12 +1914,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x70 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         data: { 8 },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for AdaptationFieldDataDescriptor {}
+impl_descriptor!(AdaptationFieldDataDescriptor, 0x70);
 
 
 // 0x71 ServiceIdentifierDescriptor
@@ -1418,12 +1930,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x71 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         textual_service_identifier_bytes: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ServiceIdentifierDescriptor {}
+impl_descriptor!(ServiceIdentifierDescriptor, 0x71);
 
 
 // 0x72 ServiceAvailabilityDescriptor
@@ -1434,15 +1946,15 @@ bit_struct!(
         pub cell_ids: Vec<u16>
     }
     deserialize(reader) {
-        expect: { bits: 8, reference: 0x70 },
-        descriptor_length: { 8 },
+        expect: { bits: 8, reference: 0x72 },
+        descriptor_length: { 8, length_of: rest },
         available: { 1, map: bool_flag },
         reserved: { 7 },
         cell_ids: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ServiceAvailabilityDescriptor {}
+impl_descriptor!(ServiceAvailabilityDescriptor, 0x72);
 
 
 // 0x73 DefaultAuthorityDescriptor
@@ -1453,12 +1965,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x73 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         default_authority_bytes: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for DefaultAuthorityDescriptor {}
+impl_descriptor!(DefaultAuthorityDescriptor, 0x73);
 
 
 // 0x74 RelatedContentDescriptor
@@ -1472,12 +1984,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x74 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         void: { value: () },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for RelatedContentDescriptor {}
+impl_descriptor!(RelatedContentDescriptor, 0x74);
 
 
 bit_struct!(
@@ -1501,12 +2013,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x75 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         tva_ids: {  value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for TvaIdDescriptor {}
+impl_descriptor!(TvaIdDescriptor, 0x75);
 
 
 #[derive(Debug)]
@@ -1516,6 +2028,32 @@ pub enum Crid {
     UnrecognizedCridType(u8)
 }
 
+impl Crid {
+    fn crid_location(&self) -> u8 {
+        match *self {
+            Crid::Explicit(..) => 0,
+            Crid::Reference(..) => 1,
+            Crid::UnrecognizedCridType(crid_location) => crid_location,
+        }
+    }
+}
+
+impl Serialize for Crid {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()> {
+        match *self {
+            Crid::Explicit(ref bytes) => {
+                if bytes.len() > 0xff {
+                    return Err(SerializationError::InvalidValue("CRID is longer than 255 bytes"));
+                }
+                try!(writer.write_u8(8, bytes.len() as u8));
+                bytes.serialize(writer)
+            },
+            Crid::Reference(crid_ref) => crid_ref.serialize(writer),
+            Crid::UnrecognizedCridType(..) => Ok(()),
+        }
+    }
+}
+
 bit_struct!(
     #[derive(Debug)]
     pub struct TypedCrid {
@@ -1523,10 +2061,8 @@ bit_struct!(
         pub crid: Crid
     }
     deserialize(reader) {
-        expect: { bits: 8, reference: 0x76 },
-        descriptor_length: { 8 },
         crid_type: { 6 },
-        crid_location: { 2, type: u8 },
+        crid_location: { 2, type: u8, write: crid.crid_location() },
         crid: { value: match crid_location {
             0 => Crid::Explicit({
                 let crid_length = try!(reader.read_u8(8));
@@ -1534,8 +2070,7 @@ bit_struct!(
             }),
             1 => Crid::Reference(try!(reader.read_u16(16))),
             other => Crid::UnrecognizedCridType(other)
-        } },
-        skip: { bits_remaining(descriptor_length, reader) }
+        } }
     }
 );
 
@@ -1547,12 +2082,12 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x76 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         crids: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ContentIdentifierDescriptor {}
+impl_descriptor!(ContentIdentifierDescriptor, 0x76);
 
 
 // 0x77 TimeSliceFecIdentifierDescriptor
@@ -1569,7 +2104,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x77 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         time_slicing: { 1, map: bool_flag },
         mpe_fec: { 2 },
         reserved: { 2 },
@@ -1581,7 +2116,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for TimeSliceFecIdentifierDescriptor {}
+impl_descriptor!(TimeSliceFecIdentifierDescriptor, 0x77);
 
 
 // 0x78 EcmRepetitionRateDescriptor
@@ -1594,14 +2129,14 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x78 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         ca_system_id: { 16 },
         ecm_repetition_rate: { 16 },
         private_data_bytes: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for EcmRepetitionRateDescriptor {}
+impl_descriptor!(EcmRepetitionRateDescriptor, 0x78);
 
 
 // 0x79 S2SatelliteDeliverySystemDescriptor
@@ -1616,7 +2151,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x79 },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         scrambling_sequence_selector: { 1, map: bool_flag },
         multiple_input_stream: { 1, map: bool_flag },
         backwards_compatibility_indicator: { 1, map: bool_flag },
@@ -1624,14 +2159,17 @@ bit_struct!(
         scrambling_sequence_index: { value: if scrambling_sequence_selector {
             try!(::base::reserved(reader, 6));
             Some(try!(reader.read_u32(18)))
-        } else { None } },
+        } else { None }, write: scrambling_sequence_index.map(|index| {
+            // Six reserved bits and 18 bits of index
+            vec![0xfc | (index >> 16) as u8 & 0x03, (index >> 8) as u8, index as u8]
+        }) },
         input_stream_identifier: { value: if multiple_input_stream {
             Some(try!(reader.read_u8(8)))
         } else { None } },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for S2SatelliteDeliverySystemDescriptor {}
+impl_descriptor!(S2SatelliteDeliverySystemDescriptor, 0x79);
 
 
 // 0x7a EnhancedAc3Descriptor
@@ -1649,15 +2187,15 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x7a },
-        descriptor_length: { 8 },
-        component_type_flag: { 1, type: u8 },
-        bsid_flag: { 1, type: u8 },
-        mainid_flag: { 1, type: u8 },
-        asvc_flag: { 1, type: u8 },
+        descriptor_length: { 8, length_of: rest },
+        component_type_flag: { 1, type: u8, write: component_type.is_some() },
+        bsid_flag: { 1, type: u8, write: bsid.is_some() },
+        mainid_flag: { 1, type: u8, write: mainid.is_some() },
+        asvc_flag: { 1, type: u8, write: asvc.is_some() },
         mix_info_exists: { 1, map: bool_flag },
-        substream_1_flag: { 1, type: u8 },
-        substream_2_flag: { 1, type: u8 },
-        substream_3_flag: { 1, type: u8 },
+        substream_1_flag: { 1, type: u8, write: substream_1.is_some() },
+        substream_2_flag: { 1, type: u8, write: substream_2.is_some() },
+        substream_3_flag: { 1, type: u8, write: substream_3.is_some() },
         component_type: { value: if component_type_flag == 1 { Some(try!(reader.read_u8(8))) } else { None } },
         bsid: { value: if bsid_flag == 1 { Some(try!(reader.read_u8(8))) } else { None } },
         mainid: { value: if mainid_flag == 1 { Some(try!(reader.read_u8(8))) } else { None } },
@@ -1668,7 +2206,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for EnhancedAc3Descriptor {}
+impl_descriptor!(EnhancedAc3Descriptor, 0x7a);
 
 
 // 0x7b DtsDescriptor
@@ -1686,7 +2224,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x7b },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         sample_rate_code: { 4 },
         bit_rate_code: { 6 },
         nblks: { 7 },
@@ -1698,7 +2236,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for DtsDescriptor {}
+impl_descriptor!(DtsDescriptor, 0x7b);
 
 
 // 0x7c AacDescriptor
@@ -1711,7 +2249,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x7c },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         profile_and_level: { 8 },
         aac_type: { value: if descriptor_length > 1 {
             let aac_type_flag = try!(reader.read_u8(1));
@@ -1719,12 +2257,17 @@ bit_struct!(
             if aac_type_flag == 1 {
                 Some(try!(reader.read_u8(8)))
             } else { None }
-        } else { None } },
+        } else { None }, write: match *aac_type {
+            // aac_type_flag and seven reserved bits
+            Some(aac_type) => vec![0xff, aac_type],
+            None if !additional_info_bytes.is_empty() => vec![0x7f],
+            None => vec![],
+        } },
         additional_info_bytes: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for AacDescriptor {}
+impl_descriptor!(AacDescriptor, 0x7c);
 
 
 // 0x7d XaitLocationDescriptor
@@ -1738,7 +2281,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x7d },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         xait_original_network_id: { 16 },
         xait_service_id: { 16 },
         xait_version_number: { 5 },
@@ -1746,7 +2289,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for XaitLocationDescriptor {}
+impl_descriptor!(XaitLocationDescriptor, 0x7d);
 
 
 // 0x7e FtaDescriptor
@@ -1759,7 +2302,7 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x7e },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         reserved: { 4 },
         do_not_scramble: { 1, map: bool_flag },
         control_remote_access_over_internet: { 2 },
@@ -1767,7 +2310,7 @@ bit_struct!(
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for FtaDescriptor {}
+impl_descriptor!(FtaDescriptor, 0x7e);
 
 
 // 0x7f ExtensionDescriptor
@@ -1779,13 +2322,13 @@ bit_struct!(
     }
     deserialize(reader) {
         expect: { bits: 8, reference: 0x7f },
-        descriptor_length: { 8 },
+        descriptor_length: { 8, length_of: rest },
         descriptor_tag_extension: { 8 },
         selector_bytes: { value: try!(repeated_element(descriptor_length, reader)) },
         skip: { bits_remaining(descriptor_length, reader) }
     }
 );
-impl Descriptor for ExtensionDescriptor {}
+impl_descriptor!(ExtensionDescriptor, 0x7f);
 
 
 fn read_string(length: u8, reader: &mut bitreader::BitReader) -> bitreader::Result<String> {
@@ -1812,6 +2355,29 @@ fn remainder_as_string(descriptor_length: u8, reader: &mut bitreader::BitReader)
     Ok(bytes_to_string(&bytes[..]))
 }
 
+// Printable ASCII is written as is (as it is the same in the default character table), anything
+// else as UTF-8
+fn string_to_bytes(string: &str) -> Vec<u8> {
+    if string.bytes().all(|byte| byte >= 0x20 && byte < 0x7f) {
+        return string.as_bytes().to_vec();
+    }
+    let mut bytes = Vec::with_capacity(string.len() + 1);
+    bytes.push(0x15);
+    bytes.extend(string.bytes());
+    bytes
+}
+
+fn string_to_bytes_latin1(string: &str) -> SerializationResult<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(string.len());
+    for character in string.chars() {
+        if character as u32 >= 0x100 {
+            return Err(SerializationError::InvalidValue("string has characters outside ISO 8859-1"));
+        }
+        bytes.push(character as u8);
+    }
+    Ok(bytes)
+}
+
 fn bytes_to_string(bytes: &[u8]) -> String {
     if bytes.is_empty() {
         return String::new();
@@ -1866,3 +2432,112 @@ fn bytes_to_string(bytes: &[u8]) -> String {
     };
     name
 }
+
+#[cfg(test)]
+mod tests {
+    use ::base::Deserialize;
+    use super::*;
+
+    // 11.75725 GHz, 19.2° east, vertical, DVB-S2 8PSK with α = 0,35, 27.5 Msymbol/s, FEC 3/4
+    const SATELLITE: [u8; 13] = [0x43, 0x0b, 0x01, 0x17, 0x57, 0x25, 0x01, 0x92, 0xa6, 0x02, 0x75,
+        0x00, 0x03];
+
+    // 312 MHz, RS(204/188), 64-QAM, 6.9 Msymbol/s, FEC 7/8
+    const CABLE: [u8; 13] = [0x44, 0x0b, 0x03, 0x12, 0x00, 0x00, 0xff, 0xf2, 0x03, 0x00, 0x69, 0x00,
+        0x05];
+
+    // 490 MHz, 8 MHz bandwidth, 64-QAM, guard interval 1/8, 8k mode
+    const TERRESTRIAL: [u8; 13] = [0x5a, 0x0b, 0x02, 0xeb, 0xae, 0x40, 0x1f, 0x82, 0x12, 0xff, 0xff,
+        0xff, 0xff];
+
+    #[test]
+    fn service_type() {
+        assert_eq!(ServiceType::from(0x19), ServiceType::H264HdDigitalTelevision);
+        assert_eq!(u8::from(ServiceType::H264HdDigitalTelevision), 0x19);
+        assert_eq!(ServiceType::from(0x01).to_string(), "digital television service");
+        assert_eq!(ServiceType::from(0x85), ServiceType::UserDefined(0x85));
+        assert_eq!(ServiceType::from(0x85).to_string(), "user defined (0x85)");
+        assert_eq!(ServiceType::from(0x20), ServiceType::Reserved(0x20));
+        assert_eq!(u8::from(ServiceType::Reserved(0x20)), 0x20);
+    }
+
+    #[test]
+    fn component_type() {
+        // MPEG-2 video with component_tag 1 in Finnish
+        let bytes = [0x50, 0x06, 0xf1, 0x03, 0x01, 0x66, 0x69, 0x6e];
+        let descriptor = ComponentDescriptor::from_bytes(&bytes).unwrap();
+        assert_eq!(descriptor.stream_content(), StreamContent::Mpeg2Video);
+        assert_eq!(descriptor.component_type().to_string(),
+                   "MPEG-2 video, 16:9 aspect ratio without pan vectors, 25 Hz");
+
+        let user_defined = ComponentType { stream_content: StreamContent::Mpeg2Video, component_type: 0xb0 };
+        assert!(user_defined.is_user_defined());
+        assert_eq!(user_defined.description(), None);
+        assert_eq!(user_defined.to_string(), "MPEG-2 video, user defined (0xb0)");
+        let reserved = ComponentType { stream_content: StreamContent::from(0x09), component_type: 0x01 };
+        assert!(!reserved.is_user_defined());
+        assert_eq!(reserved.to_string(), "reserved for future use (0x09), component_type 0x01");
+    }
+
+    #[test]
+    fn delivery_system_fields() {
+        let satellite = SatelliteDeliverySystemDescriptor::from_bytes(&SATELLITE).unwrap();
+        assert_eq!(satellite.polarization(), Polarization::LinearVertical);
+        assert_eq!(satellite.roll_off(), Some(RollOff::Alpha035));
+        assert_eq!(satellite.modulation_type(), SatelliteModulationType::Psk8);
+        assert_eq!(satellite.fec_inner(), FecInner::Rate3_4);
+        assert_eq!(satellite.fec_inner().to_string(), "3/4 conv. code rate");
+
+        let cable = CableDeliverySystemDescriptor::from_bytes(&CABLE).unwrap();
+        assert_eq!(cable.fec_outer(), FecOuter::ReedSolomon);
+        assert_eq!(cable.modulation(), CableModulation::Qam64);
+        assert_eq!(cable.fec_inner(), FecInner::Rate7_8);
+
+        let terrestrial = TerrestrialDeliverySystemDescriptor::from_bytes(&TERRESTRIAL).unwrap();
+        assert_eq!(terrestrial.bandwidth(), Bandwidth::Mhz8);
+        assert_eq!(terrestrial.constellation(), Constellation::Qam64);
+        assert_eq!(terrestrial.guard_interval(), GuardInterval::Interval1_8);
+        assert_eq!(terrestrial.transmission_mode(), TransmissionMode::Mode8k);
+        assert_eq!(terrestrial.transmission_mode().to_string(), "8k mode");
+    }
+
+    #[test]
+    fn delivery_system_units() {
+        let satellite = SatelliteDeliverySystemDescriptor::from_bytes(&SATELLITE).unwrap();
+        assert_eq!(satellite.frequency_hz(), Some(11_757_250_000));
+        assert_eq!(satellite.orbital_position_tenths(), Some(192));
+        assert_eq!(satellite.symbol_rate_symbols_per_second(), Some(27_500_000));
+        let mut west = SATELLITE;
+        west[8] &= 0x7f;
+        let west = SatelliteDeliverySystemDescriptor::from_bytes(&west).unwrap();
+        assert_eq!(west.orbital_position_tenths(), Some(-192));
+        let mut invalid = SATELLITE;
+        invalid[5] = 0x2a;
+        let invalid = SatelliteDeliverySystemDescriptor::from_bytes(&invalid).unwrap();
+        assert_eq!(invalid.frequency_hz(), None);
+
+        let cable = CableDeliverySystemDescriptor::from_bytes(&CABLE).unwrap();
+        assert_eq!(cable.frequency_hz(), Some(312_000_000));
+        assert_eq!(cable.symbol_rate_symbols_per_second(), Some(6_900_000));
+
+        let terrestrial = TerrestrialDeliverySystemDescriptor::from_bytes(&TERRESTRIAL).unwrap();
+        assert_eq!(terrestrial.centre_frequency_hz(), 490_000_000);
+    }
+
+    #[test]
+    fn frequency_list() {
+        let terrestrial = [0x62, 0x09, 0xff, 0x02, 0xeb, 0xae, 0x40, 0x03, 0x0d, 0x40, 0x00];
+        let descriptor = FrequencyListDescriptor::from_bytes(&terrestrial).unwrap();
+        assert_eq!(descriptor.coding_type(), FrequencyCodingType::Terrestrial);
+        assert_eq!(descriptor.centre_frequencies_hz(), Some(vec![490_000_000, 512_000_000]));
+
+        let cable = [0x62, 0x05, 0xfe, 0x03, 0x12, 0x00, 0x00];
+        let descriptor = FrequencyListDescriptor::from_bytes(&cable).unwrap();
+        assert_eq!(descriptor.centre_frequencies_hz(), Some(vec![312_000_000]));
+
+        let not_defined = [0x62, 0x05, 0xfc, 0x02, 0xeb, 0xae, 0x40];
+        let descriptor = FrequencyListDescriptor::from_bytes(&not_defined).unwrap();
+        assert_eq!(descriptor.coding_type(), FrequencyCodingType::NotDefined);
+        assert_eq!(descriptor.centre_frequencies_hz(), None);
+    }
+}
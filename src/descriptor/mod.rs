@@ -12,13 +12,49 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::any::Any;
 use std::fmt::Debug;
 
 use ::base::*;
 use bitreader::BitReader;
+use bitwriter::BitWriter;
 use bitreader::Result as BitReaderResult;
 
-pub trait Descriptor: Debug {}
+pub trait Descriptor: Debug + Any + Serialize {
+    fn tag(&self) -> u8;
+    fn as_any(&self) -> &Any;
+}
+
+impl Descriptor {
+    /// The concrete descriptor, if it is of type `T`.
+    pub fn downcast_ref<T: Descriptor>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+
+    pub fn is<T: Descriptor>(&self) -> bool {
+        self.as_any().is::<T>()
+    }
+}
+
+impl Serialize for Box<Descriptor> {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()> {
+        (**self).serialize(writer)
+    }
+}
+
+macro_rules! impl_descriptor {
+    ($T:ident, $tag:expr) => (
+        impl ::descriptor::Descriptor for $T {
+            fn tag(&self) -> u8 {
+                $tag
+            }
+
+            fn as_any(&self) -> &::std::any::Any {
+                self
+            }
+        }
+    );
+}
 
 pub mod basic;
 pub mod dvb;
@@ -140,6 +176,16 @@ pub fn deserialize_descriptor(reader: &mut BitReader) -> DeserializationResult<B
     )
 }
 
+/// The first descriptor of type `T` in a descriptor loop.
+pub fn find_descriptor<T: Descriptor>(descriptors: &[Box<Descriptor>]) -> Option<&T> {
+    descriptors.iter().filter_map(|descriptor| descriptor.downcast_ref()).next()
+}
+
+/// All descriptors of type `T` in a descriptor loop, in order.
+pub fn find_descriptors<T: Descriptor>(descriptors: &[Box<Descriptor>]) -> Vec<&T> {
+    descriptors.iter().filter_map(|descriptor| descriptor.downcast_ref()).collect()
+}
+
 fn bits_remaining(descriptor_length: u8, reader: &BitReader) -> u64 {
     let intro_bits = 16; // descriptor_tag (8 bits) + descriptor_length (8 bits)
     let data_bits = descriptor_length as u64 * 8; // How many data bits after intro bits
@@ -163,3 +209,53 @@ fn read_tla(reader: &mut BitReader) -> BitReaderResult<String> {
     tla.push(try!(reader.read_u8(8)) as char);
     Ok(tla)
 }
+
+fn tla_bytes(tla: &str) -> SerializationResult<Vec<u8>> {
+    let bytes: Vec<u8> = tla.chars().filter(|c| (*c as u32) < 0x100).map(|c| c as u8).collect();
+    if bytes.len() != 3 || tla.chars().count() != 3 {
+        return Err(SerializationError::InvalidValue("three letter code must have three single byte characters"));
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::basic::{UnknownDescriptor,Iso639LanguageDescriptor};
+    use super::dvb::NetworkNameDescriptor;
+
+    // Network name "Test", user defined descriptor 0x80 and another network name "Foo"
+    const DESCRIPTORS: [u8; 13] = [0x40, 0x04, 0x54, 0x65, 0x73, 0x74, 0x80, 0x00, 0x40, 0x03, 0x46,
+        0x6f, 0x6f];
+
+    fn descriptors() -> Vec<Box<Descriptor>> {
+        let mut reader = BitReader::new(&DESCRIPTORS);
+        let mut descriptors = vec![];
+        while reader.remaining() > 0 {
+            descriptors.push(deserialize_descriptor(&mut reader).unwrap());
+        }
+        descriptors
+    }
+
+    #[test]
+    fn downcast() {
+        let descriptors = descriptors();
+        assert_eq!(descriptors.iter().map(|descriptor| descriptor.tag()).collect::<Vec<_>>(), vec![0x40, 0x80, 0x40]);
+        assert!(descriptors[0].is::<NetworkNameDescriptor>());
+        assert!(!descriptors[0].is::<UnknownDescriptor>());
+        assert_eq!(descriptors[0].downcast_ref::<NetworkNameDescriptor>().unwrap().name, "Test");
+        assert!(descriptors[1].downcast_ref::<NetworkNameDescriptor>().is_none());
+        assert_eq!(descriptors[1].downcast_ref::<UnknownDescriptor>().unwrap().descriptor_tag, 0x80);
+    }
+
+    #[test]
+    fn find() {
+        let descriptors = descriptors();
+        assert_eq!(find_descriptor::<NetworkNameDescriptor>(&descriptors).unwrap().name, "Test");
+        let names: Vec<&str> = find_descriptors::<NetworkNameDescriptor>(&descriptors).iter()
+            .map(|descriptor| &descriptor.name[..])
+            .collect();
+        assert_eq!(names, vec!["Test", "Foo"]);
+        assert!(find_descriptor::<Iso639LanguageDescriptor>(&descriptors).is_none());
+    }
+}
@@ -0,0 +1,40 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Test fixtures
+//!
+//! Sections used by the tests of several modules.
+
+// PAT of transport stream 1, version 0, with program 1 on PID 0x100
+pub const PAT: [u8; 16] = [0x00, 0xb0, 0x0d, 0x00, 0x01, 0xc1, 0x00, 0x00, 0x00, 0x01, 0xe1, 0x00,
+    0xe8, 0xf9, 0x5e, 0x7d];
+
+// Long-form section with the given header fields and body, followed by its CRC_32
+pub fn section(table_id: u8, table_id_extension: u16, version_number: u8, section_number: u8,
+               last_section_number: u8, body: &[u8]) -> Vec<u8> {
+    // The reserved bits after section_syntax_indicator are '0' in MPEG-2 and '1' in DVB tables
+    let syntax = if table_id < 0x40 { 0xb0 } else { 0xf0 };
+    let section_length = 5 + body.len() + 4;
+    let mut bytes = vec![table_id, syntax | (section_length >> 8) as u8, section_length as u8,
+        (table_id_extension >> 8) as u8, table_id_extension as u8, 0xc1 | version_number << 1,
+        section_number, last_section_number];
+    bytes.extend(body.iter().cloned());
+    append_crc(&mut bytes);
+    bytes
+}
+
+pub fn append_crc(bytes: &mut Vec<u8>) {
+    let crc = ::crc::crc32_mpeg2(&bytes[..]);
+    bytes.extend([(crc >> 24) as u8, (crc >> 16) as u8, (crc >> 8) as u8, crc as u8].iter().cloned());
+}
@@ -19,7 +19,18 @@ extern crate bitreader;
 mod macros;
 
 pub mod base;
+pub mod bitwriter;
+pub mod ts;
+pub mod pes;
+pub mod clock;
+pub mod multiplex;
+pub mod component;
 pub mod section;
 pub mod descriptor;
+pub mod time;
 
 mod encodings;
+mod crc;
+
+#[cfg(test)]
+mod fixtures;
@@ -28,30 +28,52 @@ macro_rules! bit_struct {
         }
         impl ::base::Deserialize for $struct_name {
             fn deserialize(original_reader: &mut ::bitreader::BitReader) -> ::base::DeserializationResult<$struct_name> {
+                ::base::Deserialize::deserialize_with_options(original_reader, &::base::DeserializeOptions::new())
+            }
+            // Only sections use the options
+            #[allow(unused_variables)]
+            fn deserialize_with_options(original_reader: &mut ::bitreader::BitReader, options: &::base::DeserializeOptions) -> ::base::DeserializationResult<$struct_name> {
                 let mut relative_reader = original_reader.relative_reader();
                 let $reader = &mut relative_reader;
-                $( bit_struct!(field $field : $reader : { $($tokens)+ } ); )+
+                $( bit_struct!(field $field : $reader : original_reader : options : { $($tokens)+ } ); )+
                 try!(original_reader.skip($reader.position()));
                 Ok($struct_name {
                     $($field_name: $field_name),+
                 })
             }
         }
+        impl ::base::Serialize for $struct_name {
+            // Not all struct fields are needed, as some of them are written through other fields
+            #[allow(unused_variables)]
+            fn serialize(&self, target_writer: &mut ::bitwriter::BitWriter) -> ::base::SerializationResult<()> {
+                let $struct_name { $(ref $field_name),+ } = *self;
+                // The struct is written separately, so that length fields and CRC can refer to
+                // its beginning
+                let mut struct_writer = ::bitwriter::BitWriter::new();
+                {
+                    let writer = &mut struct_writer;
+                    $( bit_struct!(write $field : writer : { $($tokens)+ } ); )+
+                    try!(writer.close_lengths(0));
+                }
+                Ok(try!(target_writer.append(&struct_writer)))
+            }
+        }
     );
 
-    (field reserved : $reader:ident : { $bits:expr }) => (
+    (field reserved : $reader:ident : $start:ident : $options:ident : { $bits:expr }) => (
         try!(::base::reserved($reader, $bits))
     );
 
-    (field expect : $reader:ident : { bits: $bits:expr, reference: $value:expr }) => (
+    (field expect : $reader:ident : $start:ident : $options:ident : { bits: $bits:expr, reference: $value:expr }) => (
         try!(::base::expect($reader, $bits, $value))
     );
 
-    (field crc : $reader:ident : { 32 }) => (
-        try!($reader.skip(32));
+    (field crc : $reader:ident : $start:ident : $options:ident : { 32 }) => (
+        // $start has not been advanced yet, so it still points to the beginning of the section
+        try!(::base::verify_crc($start.relative_reader(), $reader, $options));
     );
 
-    (field skip : $reader:ident : { $bits:expr }) => (
+    (field skip : $reader:ident : $start:ident : $options:ident : { $bits:expr }) => (
         {
             // Evaluate $bits before using the result, as $bits might use $reader which would
             // cause a borrow conflict.
@@ -60,19 +82,148 @@ macro_rules! bit_struct {
         }
     );
 
-    (field $field:ident : $reader:ident : { value : $e:expr }) => (
+    (field $field:ident : $reader:ident : $start:ident : $options:ident : { value : $e:expr }) => (
+        let $field = $e;
+    );
+
+    (field $field:ident : $reader:ident : $start:ident : $options:ident : { value : $e:expr, write: $w:expr }) => (
         let $field = $e;
     );
 
-    (field $field:ident : $reader:ident : { $bits:expr }) => (
+    (field $field:ident : $reader:ident : $start:ident : $options:ident : { $bits:expr }) => (
         let $field = try!(::bitreader::ReadInto::read($reader, $bits));
     );
 
-    (field $field:ident : $reader:ident : { $bits:expr, map: $closure:expr }) => (
+    (field $field:ident : $reader:ident : $start:ident : $options:ident : { $bits:expr, map: $closure:expr }) => (
+        let $field = ($closure)(try!(::bitreader::ReadInto::read($reader, $bits)));
+    );
+
+    (field $field:ident : $reader:ident : $start:ident : $options:ident : { $bits:expr, map: $closure:expr, write: $w:expr }) => (
         let $field = ($closure)(try!(::bitreader::ReadInto::read($reader, $bits)));
     );
 
-    (field $field:ident : $reader:ident : { $bits:expr, type: $T:ty }) => (
+    (field $field:ident : $reader:ident : $start:ident : $options:ident : { $bits:expr, type: $T:ty }) => (
+        let $field: $T = try!(::bitreader::ReadInto::read($reader, $bits));
+    );
+
+    (field $field:ident : $reader:ident : $start:ident : $options:ident : { $bits:expr, type: $T:ty, $kind:ident: $w:expr }) => (
         let $field: $T = try!(::bitreader::ReadInto::read($reader, $bits));
     );
+
+    (field $field:ident : $reader:ident : $start:ident : $options:ident : { $bits:expr, $kind:ident: $w:expr }) => (
+        let $field = try!(::bitreader::ReadInto::read($reader, $bits));
+    );
+
+    // Serialization of the fields. Struct fields are available as references, other values read
+    // during deserialization need either `write` giving the value to write or `length_of` giving
+    // the data whose length in bytes is written. `length_of: rest` is the length of everything
+    // following the field in the struct.
+
+    (write reserved : $writer:ident : { $bits:expr }) => (
+        try!(::base::write_reserved($writer, $bits))
+    );
+
+    (write expect : $writer:ident : { bits: $bits:expr, reference: $value:expr }) => (
+        try!($writer.write_u64($bits, $value))
+    );
+
+    (write crc : $writer:ident : { 32 }) => (
+        // The section length covers the CRC too
+        try!($writer.close_lengths(4));
+        try!(::base::write_crc($writer));
+    );
+
+    (write skip : $writer:ident : { $bits:expr }) => ();
+
+    (write $field:ident : $writer:ident : { value : $e:expr }) => (
+        try!(::base::Serialize::serialize($field, $writer))
+    );
+
+    (write $field:ident : $writer:ident : { value : $e:expr, write: $w:expr }) => (
+        let $field = $w;
+        try!(::base::Serialize::serialize(&$field, $writer));
+    );
+
+    (write $field:ident : $writer:ident : { $bits:expr }) => (
+        try!(::bitwriter::WriteInto::write($field, $writer, $bits))
+    );
+
+    (write $field:ident : $writer:ident : { $bits:expr, map: $closure:expr }) => (
+        try!(::bitwriter::WriteInto::write($field, $writer, $bits))
+    );
+
+    (write $field:ident : $writer:ident : { $bits:expr, map: $closure:expr, write: $($w:tt)+ }) => (
+        bit_struct!(write $field : $writer : { $bits, write: $($w)+ })
+    );
+
+    (write $field:ident : $writer:ident : { $bits:expr, type: $T:ty }) => (
+        try!(::bitwriter::WriteInto::write($field, $writer, $bits))
+    );
+
+    (write $field:ident : $writer:ident : { $bits:expr, type: $T:ty, $kind:ident: $($w:tt)+ }) => (
+        bit_struct!(write $field : $writer : { $bits, $kind: $($w)+ })
+    );
+
+    (write $field:ident : $writer:ident : { $bits:expr, length_of: rest }) => (
+        try!($writer.open_length($bits))
+    );
+
+    (write $field:ident : $writer:ident : { $bits:expr, length_of: $e:expr }) => (
+        let $field = try!(::base::serialized_length(&$e));
+        try!(::bitwriter::WriteInto::write(&$field, $writer, $bits));
+    );
+
+    (write $field:ident : $writer:ident : { $bits:expr, write: $w:expr }) => (
+        let $field = $w;
+        try!(::bitwriter::WriteInto::write(&$field, $writer, $bits));
+    );
+}
+
+// An enum for a coded field, with conversions from and to the raw value and a Display impl giving
+// the meaning of the value. Values without a variant of their own fall into one of the ranges,
+// which keep the raw value.
+macro_rules! coded_enum {
+    (
+        $(#[$attr:meta])*
+        pub enum $name:ident {
+            $($value:tt => $variant:ident : $text:expr),+
+        }
+        ranges {
+            $($range:pat => $other:ident : $other_text:expr),+
+        }
+    ) => (
+        $(#[$attr])*
+        #[derive(Debug,Copy,Clone,PartialEq,Eq)]
+        pub enum $name {
+            $($variant,)+
+            $($other(u8),)+
+        }
+
+        impl From<u8> for $name {
+            fn from(value: u8) -> $name {
+                match value {
+                    $($value => $name::$variant,)+
+                    $($range => $name::$other(value),)+
+                }
+            }
+        }
+
+        impl From<$name> for u8 {
+            fn from(value: $name) -> u8 {
+                match value {
+                    $($name::$variant => $value,)+
+                    $($name::$other(value) => value,)+
+                }
+            }
+        }
+
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                match *self {
+                    $($name::$variant => write!(f, "{}", $text),)+
+                    $($name::$other(value) => write!(f, "{} (0x{:02x})", $other_text, value),)+
+                }
+            }
+        }
+    );
 }
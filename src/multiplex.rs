@@ -0,0 +1,504 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Services of a transport stream
+//!
+//! Joins PAT, PMT, SDT and NIT of the actual transport stream into a single view of its
+//! services.
+
+use std::collections::{BTreeMap,HashMap};
+use std::collections::btree_map;
+
+use ::base::{Deserialize,DeserializationResult};
+use ::section::Section;
+use ::section::psi::ProgramAssociation;
+use ::section::table::{TableCollector,TableEvent,TableKey};
+use ::component::{ComponentKind,StreamType};
+use ::ts::NULL_PID;
+use ::descriptor::{Descriptor,find_descriptor,find_descriptors};
+use ::descriptor::basic::{CaDescriptor,Iso639LanguageDescriptor};
+use ::descriptor::dvb::{ServiceDescriptor,ServiceType,NetworkNameDescriptor};
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct CaSystem {
+    pub ca_system_id: u16,
+    pub ca_pid: u16,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Component {
+    pub stream_type: StreamType,
+    pub kind: ComponentKind,
+    pub elementary_pid: u16,
+    pub language: Option<String>,
+    pub ca_systems: Vec<CaSystem>,
+}
+
+/// A service, or program in MPEG terms. The fields are filled in as the tables describing the
+/// service are received.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Service {
+    pub service_id: u16,
+    pub service_type: Option<ServiceType>,
+    pub name: Option<String>,
+    pub provider: Option<String>,
+    pub pmt_pid: Option<u16>,
+    pub pcr_pid: Option<u16>,
+    pub components: Vec<Component>,
+    pub ca_systems: Vec<CaSystem>,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum MultiplexEvent {
+    /// A table was received for the first time, or with a new version_number
+    TableVersionChanged {
+        table_id: u8,
+        table_id_extension: u16,
+        version_number: u8,
+    },
+    ServiceAdded(u16),
+    ServiceChanged(u16),
+    ServiceRemoved(u16),
+}
+
+#[derive(Debug,Clone)]
+struct ProgramMapInfo {
+    pcr_pid: u16,
+    ca_systems: Vec<CaSystem>,
+    components: Vec<Component>,
+}
+
+#[derive(Debug,Clone)]
+struct ServiceInfo {
+    service_id: u16,
+    service_type: Option<ServiceType>,
+    name: Option<String>,
+    provider: Option<String>,
+}
+
+#[derive(Debug,Clone)]
+struct ServiceDescriptionInfo {
+    transport_stream_id: u16,
+    original_network_id: u16,
+    services: Vec<ServiceInfo>,
+}
+
+#[derive(Debug,Clone)]
+struct NetworkInfo {
+    network_id: u16,
+    name: Option<String>,
+}
+
+/// Collects the services of a transport stream from its PSI/SI sections. Only tables of the
+/// actual transport stream and sections that are currently applicable are used.
+#[derive(Debug,Clone)]
+pub struct Multiplex {
+    collector: TableCollector,
+    transport_stream_id: Option<u16>,
+    associations: Vec<ProgramAssociation>,
+    pmts: HashMap<u16, ProgramMapInfo>,
+    sdt: Option<ServiceDescriptionInfo>,
+    nit: Option<NetworkInfo>,
+    services: BTreeMap<u16, Service>,
+}
+
+impl Multiplex {
+    pub fn new() -> Multiplex {
+        Multiplex {
+            collector: TableCollector::new(),
+            transport_stream_id: None,
+            associations: vec![],
+            pmts: HashMap::new(),
+            sdt: None,
+            nit: None,
+            services: BTreeMap::new(),
+        }
+    }
+
+    pub fn transport_stream_id(&self) -> Option<u16> {
+        self.transport_stream_id
+    }
+
+    pub fn original_network_id(&self) -> Option<u16> {
+        self.sdt().map(|sdt| sdt.original_network_id)
+    }
+
+    pub fn network_id(&self) -> Option<u16> {
+        self.nit.as_ref().map(|network| network.network_id)
+    }
+
+    pub fn network_name(&self) -> Option<&str> {
+        self.nit.as_ref()
+            .and_then(|network| network.name.as_ref())
+            .map(|name| &name[..])
+    }
+
+    /// Services in service_id order.
+    pub fn services(&self) -> btree_map::Values<'_, u16, Service> {
+        self.services.values()
+    }
+
+    pub fn service(&self, service_id: u16) -> Option<&Service> {
+        self.services.get(&service_id)
+    }
+
+    /// Feed a complete section to the multiplex. Tables take effect when all sections of their
+    /// version have been received. Returns the table version change caused by the section,
+    /// followed by the resulting changes of the services.
+    pub fn push(&mut self, section: &[u8]) -> DeserializationResult<Vec<MultiplexEvent>> {
+        match section.first() {
+            // PAT, PMT, NIT and SDT of the actual network and transport stream
+            Some(&0x00) | Some(&0x02) | Some(&0x40) | Some(&0x42) => {},
+            _ => return Ok(vec![]),
+        }
+        let table = match try!(self.collector.push(section)) {
+            Some(TableEvent::Completed(table)) => table,
+            Some(TableEvent::VersionChanged { table, .. }) => table,
+            None => return Ok(vec![]),
+        };
+        let mut sections = vec![];
+        for bytes in &table.sections {
+            sections.push(try!(Section::from_bytes(bytes)));
+        }
+
+        let mut events = vec![version_event(&table.key, table.version_number)];
+        match table.key.table_id {
+            0x00 => {
+                self.transport_stream_id = Some(table.key.table_id_extension);
+                self.associations = sections.iter().flat_map(|section| match *section {
+                    Section::ProgramAssociation(ref pat) => pat.associations.clone(),
+                    _ => vec![],
+                }).collect();
+                // Programs that left the PAT take their PMTs with them
+                let program_numbers = self.program_numbers();
+                let removed: Vec<u16> = self.pmts.keys()
+                    .filter(|program_number| !program_numbers.contains(program_number))
+                    .cloned()
+                    .collect();
+                for program_number in removed {
+                    self.pmts.remove(&program_number);
+                    self.collector.forget(&TableKey {
+                        table_id: 0x02,
+                        table_id_extension: program_number,
+                        transport_stream_id: None,
+                        original_network_id: None,
+                    });
+                }
+            },
+            0x02 => {
+                self.pmts.insert(table.key.table_id_extension, program_map_info(&sections));
+            },
+            0x40 => {
+                self.nit = Some(network_info(table.key.table_id_extension, &sections));
+            },
+            0x42 => {
+                self.sdt = Some(ServiceDescriptionInfo {
+                    transport_stream_id: table.key.table_id_extension,
+                    original_network_id: table.key.original_network_id.unwrap_or(0),
+                    services: service_infos(&sections),
+                });
+            },
+            _ => {},
+        }
+        self.rebuild_services(&mut events);
+        Ok(events)
+    }
+
+    // The SDT of the actual transport stream. It has to agree with the transport_stream_id of
+    // the PAT, and is trusted to do so until a PAT has been received.
+    fn sdt(&self) -> Option<&ServiceDescriptionInfo> {
+        let transport_stream_id = self.transport_stream_id;
+        self.sdt.as_ref().filter(|sdt| transport_stream_id.map_or(true, |id| id == sdt.transport_stream_id))
+    }
+
+    fn program_numbers(&self) -> Vec<u16> {
+        self.associations.iter().filter_map(|association| match *association {
+            ProgramAssociation::ProgramMapPid { program_number, .. } => Some(program_number),
+            _ => None,
+        }).collect()
+    }
+
+    fn service_ids(&self) -> Vec<u16> {
+        let mut service_ids = self.program_numbers();
+        if let Some(sdt) = self.sdt() {
+            service_ids.extend(sdt.services.iter().map(|service| service.service_id));
+        }
+        service_ids.sort();
+        service_ids.dedup();
+        service_ids
+    }
+
+    fn rebuild_services(&mut self, events: &mut Vec<MultiplexEvent>) {
+        let mut services = BTreeMap::new();
+        for service_id in self.service_ids() {
+            services.insert(service_id, self.build_service(service_id));
+        }
+        for (service_id, service) in &services {
+            match self.services.get(service_id) {
+                None => events.push(MultiplexEvent::ServiceAdded(*service_id)),
+                Some(previous) if previous != service => events.push(MultiplexEvent::ServiceChanged(*service_id)),
+                _ => {},
+            }
+        }
+        for service_id in self.services.keys() {
+            if !services.contains_key(service_id) {
+                events.push(MultiplexEvent::ServiceRemoved(*service_id));
+            }
+        }
+        self.services = services;
+    }
+
+    fn build_service(&self, service_id: u16) -> Service {
+        let mut service = Service {
+            service_id: service_id,
+            service_type: None,
+            name: None,
+            provider: None,
+            pmt_pid: None,
+            pcr_pid: None,
+            components: vec![],
+            ca_systems: vec![],
+        };
+        for association in &self.associations {
+            if let ProgramAssociation::ProgramMapPid { program_number, program_map_pid } = *association {
+                if program_number == service_id {
+                    service.pmt_pid = Some(program_map_pid);
+                }
+            }
+        }
+        if let Some(info) = self.pmts.get(&service_id) {
+            service.pcr_pid = Some(info.pcr_pid);
+            service.ca_systems = info.ca_systems.clone();
+            service.components = info.components.clone();
+        }
+        if let Some(sdt) = self.sdt() {
+            for info in &sdt.services {
+                if info.service_id == service_id {
+                    service.service_type = info.service_type;
+                    service.name = info.name.clone();
+                    service.provider = info.provider.clone();
+                }
+            }
+        }
+        service
+    }
+}
+
+fn version_event(key: &TableKey, version_number: u8) -> MultiplexEvent {
+    MultiplexEvent::TableVersionChanged {
+        table_id: key.table_id,
+        table_id_extension: key.table_id_extension,
+        version_number: version_number,
+    }
+}
+
+fn ca_systems(descriptors: &[Box<Descriptor>]) -> Vec<CaSystem> {
+    find_descriptors::<CaDescriptor>(descriptors).iter().map(|ca| CaSystem {
+        ca_system_id: ca.ca_system_id,
+        ca_pid: ca.ca_pid,
+    }).collect()
+}
+
+fn program_map_info(sections: &[Section]) -> ProgramMapInfo {
+    let mut info = ProgramMapInfo {
+        pcr_pid: NULL_PID,
+        ca_systems: vec![],
+        components: vec![],
+    };
+    for section in sections {
+        if let Section::ProgramMap(ref pmt) = *section {
+            info.pcr_pid = pmt.pcr_pid;
+            info.ca_systems.extend(ca_systems(&pmt.descriptors));
+            info.components.extend(pmt.programs.iter().map(|stream| Component {
+                stream_type: stream.stream_type(),
+                kind: stream.kind(),
+                elementary_pid: stream.elementary_pid,
+                language: find_descriptor::<Iso639LanguageDescriptor>(&stream.es_info)
+                    .and_then(|descriptor| descriptor.languages.first())
+                    .map(|language| language.language.clone()),
+                ca_systems: ca_systems(&stream.es_info),
+            }));
+        }
+    }
+    info
+}
+
+fn service_infos(sections: &[Section]) -> Vec<ServiceInfo> {
+    let mut infos = vec![];
+    for section in sections {
+        if let Section::ServiceDescription(ref sdt) = *section {
+            infos.extend(sdt.services.iter().map(|service| {
+                let descriptor = find_descriptor::<ServiceDescriptor>(&service.descriptors);
+                ServiceInfo {
+                    service_id: service.service_id,
+                    service_type: descriptor.map(|descriptor| descriptor.service_type()),
+                    name: descriptor.map(|descriptor| descriptor.service_name.clone()),
+                    provider: descriptor.map(|descriptor| descriptor.service_provider_name.clone()),
+                }
+            }));
+        }
+    }
+    infos
+}
+
+fn network_info(network_id: u16, sections: &[Section]) -> NetworkInfo {
+    let mut info = NetworkInfo {
+        network_id: network_id,
+        name: None,
+    };
+    for section in sections {
+        if let Section::NetworkInformation(ref nit) = *section {
+            if info.name.is_none() {
+                info.name = find_descriptor::<NetworkNameDescriptor>(&nit.descriptors)
+                    .map(|descriptor| descriptor.name.clone());
+            }
+        }
+    }
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::base::DeserializationError;
+    use ::descriptor::dvb::ServiceType;
+    use ::fixtures::section;
+
+    // PAT of transport stream 1 with program_map_PID 0x100 + program_number for each program
+    fn pat(version_number: u8, programs: &[u16]) -> Vec<u8> {
+        let mut body = vec![];
+        for &program_number in programs {
+            let pid = 0x100 + program_number;
+            body.extend([(program_number >> 8) as u8, program_number as u8, 0xe0 | (pid >> 8) as u8, pid as u8].iter().cloned());
+        }
+        section(0x00, 1, version_number, 0, 0, &body[..])
+    }
+
+    // PMT with PCR on PID 0x200 + program_number and no streams
+    fn pmt(program_number: u16) -> Vec<u8> {
+        let pcr_pid = 0x200 + program_number;
+        section(0x02, program_number, 0, 0, 0, &[0xe0 | (pcr_pid >> 8) as u8, pcr_pid as u8, 0xf0, 0x00])
+    }
+
+    // SDT section of the given transport stream with services without descriptors
+    fn sdt(transport_stream_id: u16, version_number: u8, section_number: u8, last_section_number: u8,
+           services: &[u16]) -> Vec<u8> {
+        let mut body = vec![0x00, 0x46, 0xff];
+        for &service_id in services {
+            body.extend([(service_id >> 8) as u8, service_id as u8, 0xfd, 0x80, 0x00].iter().cloned());
+        }
+        section(0x42, transport_stream_id, version_number, section_number, last_section_number, &body[..])
+    }
+
+    fn service_ids(multiplex: &Multiplex) -> Vec<u16> {
+        multiplex.services().map(|service| service.service_id).collect()
+    }
+
+    fn table_event(table_id: u8, table_id_extension: u16, version_number: u8) -> MultiplexEvent {
+        MultiplexEvent::TableVersionChanged {
+            table_id: table_id,
+            table_id_extension: table_id_extension,
+            version_number: version_number,
+        }
+    }
+
+    #[test]
+    fn multi_section_version_change() {
+        let mut multiplex = Multiplex::new();
+        assert_eq!(multiplex.push(&sdt(1, 0, 0, 1, &[1, 2])).unwrap(), vec![]);
+        assert_eq!(multiplex.push(&sdt(1, 0, 1, 1, &[3])).unwrap(), vec![
+            table_event(0x42, 1, 0),
+            MultiplexEvent::ServiceAdded(1),
+            MultiplexEvent::ServiceAdded(2),
+            MultiplexEvent::ServiceAdded(3),
+        ]);
+        assert_eq!(multiplex.push(&sdt(1, 0, 0, 1, &[1, 2])).unwrap(), vec![]);
+        assert_eq!(multiplex.original_network_id(), Some(0x46));
+
+        // The previous version stays in effect until the new one is complete
+        assert_eq!(multiplex.push(&sdt(1, 1, 1, 1, &[3, 4])).unwrap(), vec![]);
+        assert_eq!(service_ids(&multiplex), vec![1, 2, 3]);
+        assert_eq!(multiplex.push(&sdt(1, 1, 0, 1, &[1])).unwrap(), vec![
+            table_event(0x42, 1, 1),
+            MultiplexEvent::ServiceAdded(4),
+            MultiplexEvent::ServiceRemoved(2),
+        ]);
+        assert_eq!(service_ids(&multiplex), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn program_removed_from_pat() {
+        let mut multiplex = Multiplex::new();
+        multiplex.push(&pat(0, &[1, 2])).unwrap();
+        assert_eq!(multiplex.service(2).unwrap().pmt_pid, Some(0x102));
+        assert_eq!(multiplex.push(&pmt(2)).unwrap(), vec![
+            table_event(0x02, 2, 0),
+            MultiplexEvent::ServiceChanged(2),
+        ]);
+        assert_eq!(multiplex.service(2).unwrap().pcr_pid, Some(0x202));
+
+        assert_eq!(multiplex.push(&pat(1, &[1])).unwrap(), vec![
+            table_event(0x00, 1, 1),
+            MultiplexEvent::ServiceRemoved(2),
+        ]);
+        assert!(!multiplex.pmts.contains_key(&2));
+        assert_eq!(service_ids(&multiplex), vec![1]);
+
+        // The PMT of a program that returns is used again
+        multiplex.push(&pat(2, &[1, 2])).unwrap();
+        assert_eq!(multiplex.service(2).unwrap().pcr_pid, None);
+        multiplex.push(&pmt(2)).unwrap();
+        assert_eq!(multiplex.service(2).unwrap().pcr_pid, Some(0x202));
+    }
+
+    #[test]
+    fn sdt_of_other_transport_stream() {
+        let mut multiplex = Multiplex::new();
+        multiplex.push(&pat(0, &[1])).unwrap();
+        // An "actual" SDT that does not agree with the PAT is not used
+        assert_eq!(multiplex.push(&sdt(2, 0, 0, 0, &[5])).unwrap(), vec![table_event(0x42, 2, 0)]);
+        assert_eq!(service_ids(&multiplex), vec![1]);
+        assert_eq!(multiplex.original_network_id(), None);
+        assert_eq!(multiplex.push(&sdt(1, 0, 0, 0, &[1, 5])).unwrap(), vec![
+            table_event(0x42, 1, 0),
+            MultiplexEvent::ServiceAdded(5),
+        ]);
+        assert_eq!(multiplex.original_network_id(), Some(0x46));
+    }
+
+    #[test]
+    fn service_type() {
+        let mut multiplex = Multiplex::new();
+        // Service 1 with a service_descriptor for a digital television service "TV" of "P"
+        let body = [0x00, 0x46, 0xff, 0x00, 0x01, 0xfd, 0x80, 0x08, 0x48, 0x06, 0x01, 0x01, 0x50,
+            0x02, 0x54, 0x56];
+        multiplex.push(&section(0x42, 1, 0, 0, 0, &body)).unwrap();
+        let service = multiplex.service(1).unwrap();
+        assert_eq!(service.service_type, Some(ServiceType::DigitalTelevision));
+        assert_eq!(service.name, Some("TV".to_string()));
+        assert_eq!(service.provider, Some("P".to_string()));
+    }
+
+    #[test]
+    fn section_length_too_short() {
+        let mut multiplex = Multiplex::new();
+        let mut bytes = sdt(1, 0, 0, 0, &[1]);
+        bytes[2] = 0x03;
+        match multiplex.push(&bytes) {
+            Err(DeserializationError::ReadTooMuch { .. }) => {},
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(multiplex.services().next().is_none());
+    }
+}
@@ -0,0 +1,647 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Packetized Elementary Stream (PES) packets
+
+use ::base::*;
+use ::ts::{TsPacket,ClockReference,Continuity,ContinuityTracker};
+use bitreader::BitReader;
+use bitwriter::BitWriter;
+
+pub const PROGRAM_STREAM_MAP: u8 = 0xbc;
+pub const PADDING_STREAM: u8 = 0xbe;
+pub const PRIVATE_STREAM_2: u8 = 0xbf;
+pub const ECM_STREAM: u8 = 0xf0;
+pub const EMM_STREAM: u8 = 0xf1;
+pub const DSMCC_STREAM: u8 = 0xf2;
+pub const H222_1_TYPE_E_STREAM: u8 = 0xf8;
+pub const PROGRAM_STREAM_DIRECTORY: u8 = 0xff;
+
+/// Whether packets of the stream have the optional PES header.
+pub fn has_pes_header(stream_id: u8) -> bool {
+    match stream_id {
+        PROGRAM_STREAM_MAP | PADDING_STREAM | PRIVATE_STREAM_2 | ECM_STREAM | EMM_STREAM |
+            DSMCC_STREAM | H222_1_TYPE_E_STREAM | PROGRAM_STREAM_DIRECTORY => false,
+        _ => true,
+    }
+}
+
+// A PES packet. pes_packet_length is zero for video packets of unbounded length, in which case
+// the payload extends to the end of the data. Otherwise it is recomputed when serializing.
+bit_struct!(
+    #[derive(Debug,Clone)]
+    pub struct PesPacket {
+        pub stream_id: u8,
+        pub pes_packet_length: u16,
+        pub header: Option<PesHeader>,
+        pub payload: Vec<u8>
+    }
+    deserialize(reader) {
+        expect: { bits: 24, reference: 0x000001 }, // packet_start_code_prefix
+        stream_id: { 8 },
+        pes_packet_length: { 16, write: if *pes_packet_length == 0 {
+            0
+        } else {
+            try!(packet_length(header, payload))
+        } },
+        header: { value: if has_pes_header(stream_id) {
+            Some(try!(Deserialize::deserialize(reader)))
+        } else { None } },
+        payload: { value: try!(read_payload(pes_packet_length, reader)) }
+    }
+);
+
+// Timestamps (PTS, DTS, ESCR base) are 33-bit values in 90 kHz units
+bit_struct!(
+    #[derive(Debug,Clone)]
+    pub struct PesHeader {
+        pub scrambling_control: u8,
+        pub priority: bool,
+        pub data_alignment_indicator: bool,
+        pub copyright: bool,
+        pub original_or_copy: bool,
+        pub pts: Option<u64>,
+        pub dts: Option<u64>,
+        pub escr: Option<ClockReference>,
+        pub es_rate: Option<u32>,
+        pub dsm_trick_mode: Option<TrickMode>,
+        pub additional_copy_info: Option<u8>,
+        pub previous_pes_packet_crc: Option<u16>,
+        pub extension: Option<PesExtension>
+    }
+    deserialize(reader) {
+        expect: { bits: 2, reference: 0b10 },
+        scrambling_control: { 2 },
+        priority: { 1, map: bool_flag },
+        data_alignment_indicator: { 1, map: bool_flag },
+        copyright: { 1, map: bool_flag },
+        original_or_copy: { 1, map: bool_flag },
+        pts_dts_flags: { 2, type: u8, write: if dts.is_some() { 0b11u8 } else if pts.is_some() { 0b10 } else { 0 } },
+        escr_flag: { 1, map: bool_flag, write: escr.is_some() },
+        es_rate_flag: { 1, map: bool_flag, write: es_rate.is_some() },
+        dsm_trick_mode_flag: { 1, map: bool_flag, write: dsm_trick_mode.is_some() },
+        additional_copy_info_flag: { 1, map: bool_flag, write: additional_copy_info.is_some() },
+        pes_crc_flag: { 1, map: bool_flag, write: previous_pes_packet_crc.is_some() },
+        pes_extension_flag: { 1, map: bool_flag, write: extension.is_some() },
+        pes_header_data_length: { 8, type: u8, length_of: rest },
+        pts: { value: if pts_dts_flags & 0b10 != 0 {
+            // '0010' for PTS only, '0011' when DTS follows
+            try!(expect(reader, 4, pts_dts_flags as u64));
+            Some(try!(read_timestamp(reader)))
+        } else { None }, write: match *pts {
+            Some(pts) => Some(try!(timestamp_bytes(if dts.is_some() { 0b0011 } else { 0b0010 }, pts))),
+            None => None,
+        } },
+        dts: { value: if pts_dts_flags == 0b11 {
+            try!(expect(reader, 4, 0b0001));
+            Some(try!(read_timestamp(reader)))
+        } else { None }, write: match *dts {
+            Some(dts) => Some(try!(timestamp_bytes(0b0001, dts))),
+            None => None,
+        } },
+        escr: { value: if escr_flag {
+            try!(reserved(reader, 2));
+            let base = try!(read_timestamp(reader));
+            let extension = try!(reader.read_u16(9));
+            try!(expect(reader, 1, 1)); // marker_bit
+            Some(ClockReference { base: base, extension: extension })
+        } else { None }, write: match *escr {
+            Some(escr) => Some(try!(packed_bits(&[(2, 0b11), (3, escr.base >> 30), (1, 1),
+                (15, escr.base >> 15 & 0x7fff), (1, 1), (15, escr.base & 0x7fff), (1, 1),
+                (9, escr.extension as u64), (1, 1)]))),
+            None => None,
+        } },
+        es_rate: { value: if es_rate_flag {
+            try!(expect(reader, 1, 1)); // marker_bit
+            let es_rate = try!(reader.read_u32(22));
+            try!(expect(reader, 1, 1)); // marker_bit
+            Some(es_rate)
+        } else { None }, write: match *es_rate {
+            Some(es_rate) => Some(try!(packed_bits(&[(1, 1), (22, es_rate as u64), (1, 1)]))),
+            None => None,
+        } },
+        dsm_trick_mode: { value: if dsm_trick_mode_flag {
+            Some(try!(Deserialize::deserialize(reader)))
+        } else { None } },
+        additional_copy_info: { value: if additional_copy_info_flag {
+            try!(expect(reader, 1, 1)); // marker_bit
+            Some(try!(reader.read_u8(7)))
+        } else { None }, write: match *additional_copy_info {
+            Some(info) => Some(try!(packed_bits(&[(1, 1), (7, info as u64)]))),
+            None => None,
+        } },
+        previous_pes_packet_crc: { value: if pes_crc_flag {
+            Some(try!(reader.read_u16(16)))
+        } else { None } },
+        extension: { value: if pes_extension_flag {
+            Some(try!(Deserialize::deserialize(reader)))
+        } else { None } },
+        skip: { try!(bits_remaining(3, pes_header_data_length as u64, reader)) } // stuffing_bytes
+    }
+);
+
+#[derive(Debug,Clone,Copy)]
+pub enum TrickMode {
+    FastForward {
+        field_id: u8,
+        intra_slice_refresh: bool,
+        frequency_truncation: u8,
+    },
+    SlowMotion {
+        rep_cntrl: u8,
+    },
+    FreezeFrame {
+        field_id: u8,
+    },
+    FastReverse {
+        field_id: u8,
+        intra_slice_refresh: bool,
+        frequency_truncation: u8,
+    },
+    SlowReverse {
+        rep_cntrl: u8,
+    },
+    Reserved {
+        trick_mode_control: u8,
+        bits: u8,
+    },
+}
+
+impl Deserialize for TrickMode {
+    fn deserialize(original_reader: &mut BitReader) -> DeserializationResult<Self> {
+        let mut reader = original_reader.relative_reader();
+        let trick_mode_control = try!(reader.read_u8(3));
+        let trick_mode = match trick_mode_control {
+            0b000 | 0b011 => {
+                let field_id = try!(reader.read_u8(2));
+                let intra_slice_refresh = bool_flag(try!(reader.read_u8(1)));
+                let frequency_truncation = try!(reader.read_u8(2));
+                if trick_mode_control == 0b000 {
+                    TrickMode::FastForward {
+                        field_id: field_id,
+                        intra_slice_refresh: intra_slice_refresh,
+                        frequency_truncation: frequency_truncation,
+                    }
+                } else {
+                    TrickMode::FastReverse {
+                        field_id: field_id,
+                        intra_slice_refresh: intra_slice_refresh,
+                        frequency_truncation: frequency_truncation,
+                    }
+                }
+            },
+            0b001 => TrickMode::SlowMotion {
+                rep_cntrl: try!(reader.read_u8(5)),
+            },
+            0b100 => TrickMode::SlowReverse {
+                rep_cntrl: try!(reader.read_u8(5)),
+            },
+            0b010 => {
+                let field_id = try!(reader.read_u8(2));
+                try!(reserved(&mut reader, 3));
+                TrickMode::FreezeFrame {
+                    field_id: field_id,
+                }
+            },
+            _ => TrickMode::Reserved {
+                trick_mode_control: trick_mode_control,
+                bits: try!(reader.read_u8(5)),
+            },
+        };
+        try!(original_reader.skip(reader.position()));
+        Ok(trick_mode)
+    }
+}
+
+impl Serialize for TrickMode {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()> {
+        match *self {
+            TrickMode::FastForward { field_id, intra_slice_refresh, frequency_truncation } |
+            TrickMode::FastReverse { field_id, intra_slice_refresh, frequency_truncation } => {
+                let trick_mode_control = match *self {
+                    TrickMode::FastForward { .. } => 0b000,
+                    _ => 0b011,
+                };
+                try!(writer.write_u8(3, trick_mode_control));
+                try!(writer.write_u8(2, field_id));
+                try!(writer.write_bool(intra_slice_refresh));
+                try!(writer.write_u8(2, frequency_truncation));
+            },
+            TrickMode::SlowMotion { rep_cntrl } => {
+                try!(writer.write_u8(3, 0b001));
+                try!(writer.write_u8(5, rep_cntrl));
+            },
+            TrickMode::SlowReverse { rep_cntrl } => {
+                try!(writer.write_u8(3, 0b100));
+                try!(writer.write_u8(5, rep_cntrl));
+            },
+            TrickMode::FreezeFrame { field_id } => {
+                try!(writer.write_u8(3, 0b010));
+                try!(writer.write_u8(2, field_id));
+                try!(write_reserved(writer, 3));
+            },
+            TrickMode::Reserved { trick_mode_control, bits } => {
+                try!(writer.write_u8(3, trick_mode_control));
+                try!(writer.write_u8(5, bits));
+            },
+        }
+        Ok(())
+    }
+}
+
+bit_struct!(
+    #[derive(Debug,Clone)]
+    pub struct PesExtension {
+        pub private_data: Option<[u8; 16]>,
+        pub pack_header: Option<Vec<u8>>,
+        pub program_packet_sequence_counter: Option<ProgramPacketSequenceCounter>,
+        pub p_std_buffer: Option<PStdBuffer>,
+        pub extension_2: Option<PesExtension2>
+    }
+    deserialize(reader) {
+        pes_private_data_flag: { 1, map: bool_flag, write: private_data.is_some() },
+        pack_header_field_flag: { 1, map: bool_flag, write: pack_header.is_some() },
+        program_packet_sequence_counter_flag: { 1, map: bool_flag, write: program_packet_sequence_counter.is_some() },
+        p_std_buffer_flag: { 1, map: bool_flag, write: p_std_buffer.is_some() },
+        reserved: { 3 },
+        pes_extension_flag_2: { 1, map: bool_flag, write: extension_2.is_some() },
+        private_data: { value: if pes_private_data_flag {
+            let mut data = [0; 16];
+            for byte in data.iter_mut() {
+                *byte = try!(reader.read_u8(8));
+            }
+            Some(data)
+        } else { None }, write: private_data.map(|data| data.to_vec()) },
+        pack_header: { value: if pack_header_field_flag {
+            let pack_field_length = try!(reader.read_u8(8));
+            let mut pack_header = vec![];
+            for _ in 0..pack_field_length {
+                pack_header.push(try!(reader.read_u8(8)));
+            }
+            Some(pack_header)
+        } else { None }, write: match *pack_header {
+            Some(ref pack_header) => Some(try!(length_prefixed(pack_header))),
+            None => None,
+        } },
+        program_packet_sequence_counter: { value: if program_packet_sequence_counter_flag {
+            Some(try!(Deserialize::deserialize(reader)))
+        } else { None } },
+        p_std_buffer: { value: if p_std_buffer_flag {
+            Some(try!(Deserialize::deserialize(reader)))
+        } else { None } },
+        extension_2: { value: if pes_extension_flag_2 {
+            Some(try!(Deserialize::deserialize(reader)))
+        } else { None } }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug,Clone,Copy)]
+    pub struct ProgramPacketSequenceCounter {
+        pub program_packet_sequence_counter: u8,
+        pub mpeg1_mpeg2_identifier: bool,
+        pub original_stuff_length: u8
+    }
+    deserialize(reader) {
+        expect: { bits: 1, reference: 1 }, // marker_bit
+        program_packet_sequence_counter: { 7 },
+        expect: { bits: 1, reference: 1 }, // marker_bit
+        mpeg1_mpeg2_identifier: { 1, map: bool_flag },
+        original_stuff_length: { 6 }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug,Clone,Copy)]
+    pub struct PStdBuffer {
+        pub p_std_buffer_scale: bool,
+        pub p_std_buffer_size: u16
+    }
+    deserialize(reader) {
+        expect: { bits: 2, reference: 0b01 },
+        p_std_buffer_scale: { 1, map: bool_flag },
+        p_std_buffer_size: { 13 }
+    }
+);
+
+impl PStdBuffer {
+    /// Buffer size in bytes. The scale is 1024 bytes for video streams and 128 bytes for audio.
+    pub fn size_in_bytes(&self) -> u32 {
+        let unit = if self.p_std_buffer_scale { 1024 } else { 128 };
+        self.p_std_buffer_size as u32 * unit
+    }
+}
+
+// TREF is present only when there is no stream_id_extension
+bit_struct!(
+    #[derive(Debug,Clone,Copy)]
+    pub struct PesExtension2 {
+        pub stream_id_extension: Option<u8>,
+        pub tref: Option<u64>
+    }
+    deserialize(reader) {
+        expect: { bits: 1, reference: 1 }, // marker_bit
+        pes_extension_field_length: { 7, type: u8, length_of: rest },
+        stream_id_extension_flag: { 1, map: bool_flag, write: stream_id_extension.is_none() },
+        extension_bits: { 7, type: u8, write: match *stream_id_extension {
+            Some(stream_id_extension) => stream_id_extension,
+            // Reserved bits and tref_extension_flag, which is zero when TREF is present
+            None => if tref.is_some() { 0x7e } else { 0x7f },
+        } },
+        stream_id_extension: { value: if stream_id_extension_flag { None } else { Some(extension_bits) }, write: () },
+        tref: { value: if stream_id_extension_flag && extension_bits & 0x01 == 0 {
+            try!(reserved(reader, 4));
+            Some(try!(read_timestamp(reader)))
+        } else { None }, write: match *tref {
+            Some(tref) if stream_id_extension_flag => Some(try!(timestamp_bytes(0b1111, tref))),
+            _ => None,
+        } },
+        skip: { try!(bits_remaining(1, pes_extension_field_length as u64, reader)) }
+    }
+);
+
+fn packet_length(header: &Option<PesHeader>, payload: &[u8]) -> SerializationResult<u16> {
+    let length = try!(serialized_length(header)) + payload.len() as u64;
+    if length > 0xffff {
+        return Err(SerializationError::InvalidValue("bounded PES packet is longer than 65535 bytes"));
+    }
+    Ok(length as u16)
+}
+
+// A 33-bit timestamp in three parts, each followed by a marker bit
+fn read_timestamp(reader: &mut BitReader) -> DeserializationResult<u64> {
+    let high = try!(reader.read_u64(3));
+    try!(expect(reader, 1, 1));
+    let middle = try!(reader.read_u64(15));
+    try!(expect(reader, 1, 1));
+    let low = try!(reader.read_u64(15));
+    try!(expect(reader, 1, 1));
+    Ok(high << 30 | middle << 15 | low)
+}
+
+fn timestamp_bytes(prefix: u8, timestamp: u64) -> SerializationResult<Vec<u8>> {
+    packed_bits(&[(4, prefix as u64), (3, timestamp >> 30), (1, 1), (15, timestamp >> 15 & 0x7fff),
+        (1, 1), (15, timestamp & 0x7fff), (1, 1)])
+}
+
+// Fields given as (bits, value) written into whole bytes
+fn packed_bits(fields: &[(u8, u64)]) -> SerializationResult<Vec<u8>> {
+    let mut writer = BitWriter::new();
+    for &(bits, value) in fields {
+        try!(writer.write_u64(bits, value));
+    }
+    if !writer.is_aligned() {
+        return Err(SerializationError::NotByteAligned {
+            position: writer.position(),
+        });
+    }
+    Ok(writer.into_bytes())
+}
+
+// Remaining bits in a structure with a length field counting the bytes after the first
+// `header_bytes` bytes
+fn bits_remaining(header_bytes: u64, length: u64, reader: &BitReader) -> DeserializationResult<u64> {
+    let total_bits = (header_bytes + length) * 8;
+    if reader.position() > total_bits {
+        return Err(DeserializationError::ReadTooMuch {
+            position: reader.position(),
+            max_position: total_bits,
+        });
+    }
+    Ok(total_bits - reader.position())
+}
+
+fn read_payload(pes_packet_length: u16, reader: &mut BitReader) -> DeserializationResult<Vec<u8>> {
+    let bytes = if pes_packet_length == 0 {
+        reader.remaining() / 8
+    } else {
+        // packet_start_code_prefix, stream_id and PES_packet_length
+        try!(bits_remaining(6, pes_packet_length as u64, reader)) / 8
+    };
+    let mut payload = Vec::with_capacity(bytes as usize);
+    for _ in 0..bytes {
+        payload.push(try!(reader.read_u8(8)));
+    }
+    Ok(payload)
+}
+
+/// Collects the PES packets carried on a single PID from consecutive TS packets. A packet of
+/// unbounded length is complete when the next packet starts, or when `flush` is called.
+#[derive(Debug,Clone)]
+pub struct PesAssembler {
+    pid: u16,
+    continuity: ContinuityTracker,
+    buffer: Vec<u8>,
+    in_packet: bool,
+}
+
+impl PesAssembler {
+    pub fn new(pid: u16) -> PesAssembler {
+        PesAssembler {
+            pid: pid,
+            continuity: ContinuityTracker::new(),
+            buffer: vec![],
+            in_packet: false,
+        }
+    }
+
+    pub fn pid(&self) -> u16 {
+        self.pid
+    }
+
+    /// Throw away any partially collected packet.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.in_packet = false;
+    }
+
+    /// Feed a TS packet to the assembler, returning the PES packets completed by it. Packets of
+    /// other PIDs are ignored. A gap in the continuity counter discards the PES packet being
+    /// collected.
+    pub fn push(&mut self, packet: &TsPacket) -> Vec<Vec<u8>> {
+        let mut packets = vec![];
+        if packet.pid != self.pid {
+            return packets;
+        }
+        match self.continuity.push(packet) {
+            Continuity::Continuous => {},
+            Continuity::Discontinuous => self.reset(),
+            Continuity::NoPayload => return packets,
+            Continuity::TransportError => {
+                self.reset();
+                return packets;
+            },
+        }
+
+        if packet.payload_unit_start_indicator {
+            // The previous packet ends here if it was unbounded, otherwise it is incomplete
+            if let Some(previous) = self.flush() {
+                packets.push(previous);
+            }
+            self.reset();
+            self.in_packet = true;
+            self.buffer.extend(packet.payload.iter().cloned());
+        } else if self.in_packet {
+            self.buffer.extend(packet.payload.iter().cloned());
+        }
+
+        if let Some(length) = pes_packet_length(&self.buffer[..]) {
+            if self.in_packet && length > 0 && self.buffer.len() >= 6 + length {
+                // Anything after a bounded packet is stuffing
+                self.buffer.truncate(6 + length);
+                packets.push(::std::mem::replace(&mut self.buffer, vec![]));
+                self.in_packet = false;
+            }
+        }
+        packets
+    }
+
+    /// Take the packet of unbounded length being collected, such as at the end of the stream.
+    pub fn flush(&mut self) -> Option<Vec<u8>> {
+        if self.in_packet && pes_packet_length(&self.buffer[..]) == Some(0) {
+            self.in_packet = false;
+            return Some(::std::mem::replace(&mut self.buffer, vec![]));
+        }
+        None
+    }
+}
+
+fn pes_packet_length(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() < 6 {
+        return None;
+    }
+    Some((bytes[4] as usize) << 8 | bytes[5] as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::base::*;
+    use ::ts::AdaptationField;
+    use super::*;
+
+    // Video PES packet with data_alignment_indicator and a PTS of 0x12345678
+    const PES: [u8; 18] = [0x00, 0x00, 0x01, 0xe0, 0x00, 0x0c, 0x84, 0x80, 0x05, 0x21, 0x48, 0xd1,
+        0xac, 0xf1, 0xaa, 0xbb, 0xcc, 0xdd];
+
+    // Video PES packet with a 33-bit PTS of 0x123456789 and a DTS of 0x123450000
+    const PES_DTS: [u8; 21] = [0x00, 0x00, 0x01, 0xe0, 0x00, 0x0f, 0x80, 0xc0, 0x0a, 0x39, 0x8d,
+        0x15, 0xcf, 0x13, 0x19, 0x8d, 0x15, 0x00, 0x01, 0xaa, 0xbb];
+
+    #[test]
+    fn pes_round_trip() {
+        let packet = PesPacket::from_bytes(&PES).unwrap();
+        assert_eq!(packet.stream_id, 0xe0);
+        assert_eq!(packet.pes_packet_length, 12);
+        assert_eq!(packet.payload, vec![0xaa, 0xbb, 0xcc, 0xdd]);
+        {
+            let header = packet.header.as_ref().unwrap();
+            assert!(header.data_alignment_indicator);
+            assert_eq!(header.pts, Some(0x12345678));
+            assert_eq!(header.dts, None);
+        }
+        assert_eq!(packet.to_bytes().unwrap(), PES.to_vec());
+    }
+
+    #[test]
+    fn pts_and_dts() {
+        let packet = PesPacket::from_bytes(&PES_DTS).unwrap();
+        {
+            let header = packet.header.as_ref().unwrap();
+            assert_eq!(header.pts, Some(0x123456789));
+            assert_eq!(header.dts, Some(0x123450000));
+        }
+        assert_eq!(packet.payload, vec![0xaa, 0xbb]);
+        assert_eq!(packet.to_bytes().unwrap(), PES_DTS.to_vec());
+    }
+
+    #[test]
+    fn without_header() {
+        let bytes = [0x00, 0x00, 0x01, PADDING_STREAM, 0x00, 0x03, 0xff, 0xff, 0xff];
+        let packet = PesPacket::from_bytes(&bytes).unwrap();
+        assert!(packet.header.is_none());
+        assert_eq!(packet.payload, vec![0xff; 3]);
+        assert_eq!(packet.to_bytes().unwrap(), bytes.to_vec());
+    }
+
+    // TS packets carrying the PES packet, the last one stuffed through its adaptation field
+    fn ts_packets(pes: &[u8], first_continuity_counter: u8) -> Vec<TsPacket> {
+        pes.chunks(184).enumerate().map(|(i, chunk)| {
+            let adaptation_field = if chunk.len() < 184 {
+                Some(AdaptationField {
+                    discontinuity_indicator: false,
+                    random_access_indicator: false,
+                    elementary_stream_priority_indicator: false,
+                    pcr: None,
+                    opcr: None,
+                    splice_countdown: None,
+                    transport_private_data: None,
+                    extension: None,
+                })
+            } else {
+                None
+            };
+            let packet = TsPacket {
+                transport_error_indicator: false,
+                payload_unit_start_indicator: i == 0,
+                transport_priority: false,
+                pid: 0x100,
+                transport_scrambling_control: 0,
+                adaptation_field_control: if adaptation_field.is_some() { 0b11 } else { 0b01 },
+                continuity_counter: (first_continuity_counter + i as u8) & 0xf,
+                adaptation_field: adaptation_field,
+                payload: chunk.to_vec(),
+            };
+            TsPacket::from_bytes(&packet.to_bytes().unwrap()).unwrap()
+        }).collect()
+    }
+
+    // Audio PES packet with an empty header and the given payload size
+    fn audio_pes(payload_size: usize, bounded: bool) -> Vec<u8> {
+        let length = if bounded { 3 + payload_size } else { 0 };
+        let mut pes = vec![0x00, 0x00, 0x01, 0xc0, (length >> 8) as u8, length as u8, 0x80, 0x00, 0x00];
+        pes.extend((0..payload_size).map(|i| i as u8));
+        pes
+    }
+
+    #[test]
+    fn assembler() {
+        let mut assembler = PesAssembler::new(0x100);
+        let pes = audio_pes(400, true);
+        let packets = ts_packets(&pes, 0);
+        assert!(assembler.push(&packets[0]).is_empty());
+        assert!(assembler.push(&packets[1]).is_empty());
+        assert_eq!(assembler.push(&packets[2]), vec![pes.clone()]);
+
+        // A lost packet discards the PES packet
+        let packets = ts_packets(&pes, 3);
+        assembler.push(&packets[0]);
+        assert!(assembler.push(&packets[2]).is_empty());
+        assert!(assembler.flush().is_none());
+    }
+
+    #[test]
+    fn unbounded_packets() {
+        let mut assembler = PesAssembler::new(0x100);
+        let first = audio_pes(368 - 9, false);
+        let second = audio_pes(100, false);
+        for packet in &ts_packets(&first, 0) {
+            assert!(assembler.push(packet).is_empty());
+        }
+        // The next payload_unit_start_indicator ends an unbounded packet
+        let packets = ts_packets(&second, 2);
+        assert_eq!(assembler.push(&packets[0]), vec![first]);
+        assert_eq!(assembler.flush(), Some(second));
+        assert!(assembler.flush().is_none());
+    }
+}
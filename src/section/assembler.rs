@@ -0,0 +1,200 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Section reassembly from transport stream packets
+
+use ::base::*;
+use ::ts::{TsPacket,Continuity,ContinuityTracker};
+use super::PrivateSectionHeader;
+
+const STUFFING_BYTE: u8 = 0xff;
+
+/// Collects the sections carried on a single PID from consecutive TS packets.
+#[derive(Debug,Clone)]
+pub struct SectionAssembler {
+    pid: u16,
+    continuity: ContinuityTracker,
+    buffer: Vec<u8>,
+    in_section: bool,
+}
+
+impl SectionAssembler {
+    pub fn new(pid: u16) -> SectionAssembler {
+        SectionAssembler {
+            pid: pid,
+            continuity: ContinuityTracker::new(),
+            buffer: vec![],
+            in_section: false,
+        }
+    }
+
+    pub fn pid(&self) -> u16 {
+        self.pid
+    }
+
+    /// Throw away any partially collected section.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.in_section = false;
+    }
+
+    /// Feed a packet to the assembler, returning the sections completed by it. Packets of other
+    /// PIDs are ignored. A gap in the continuity counter discards the section being collected.
+    pub fn push(&mut self, packet: &TsPacket) -> Vec<Vec<u8>> {
+        let mut sections = vec![];
+        if packet.pid != self.pid {
+            return sections;
+        }
+        match self.continuity.push(packet) {
+            Continuity::Continuous => {},
+            Continuity::Discontinuous => self.reset(),
+            Continuity::NoPayload => return sections,
+            Continuity::TransportError => {
+                self.reset();
+                return sections;
+            },
+        }
+
+        let payload = &packet.payload[..];
+        if packet.payload_unit_start_indicator {
+            if payload.is_empty() {
+                self.reset();
+                return sections;
+            }
+            let pointer_field = payload[0] as usize;
+            if 1 + pointer_field > payload.len() {
+                self.reset();
+                return sections;
+            }
+            if self.in_section {
+                self.buffer.extend(payload[1..1 + pointer_field].iter().cloned());
+                self.take_sections(&mut sections);
+            }
+            self.reset();
+            self.in_section = true;
+            self.buffer.extend(payload[1 + pointer_field..].iter().cloned());
+        } else if self.in_section {
+            self.buffer.extend(payload.iter().cloned());
+        }
+        self.take_sections(&mut sections);
+        sections
+    }
+
+    fn take_sections(&mut self, sections: &mut Vec<Vec<u8>>) {
+        while self.in_section {
+            if self.buffer.first() == Some(&STUFFING_BYTE) {
+                // The rest of the packet is stuffing
+                self.reset();
+                break;
+            }
+            let size = match section_size(&self.buffer[..]) {
+                Ok(size) if size <= self.buffer.len() => size,
+                Ok(_) => break,
+                // The header has not been received completely yet
+                Err(DeserializationError::BitReaderError(..)) => break,
+                Err(_) => {
+                    self.reset();
+                    break;
+                },
+            };
+            let remainder = self.buffer.split_off(size);
+            sections.push(::std::mem::replace(&mut self.buffer, remainder));
+            if self.buffer.is_empty() {
+                self.in_section = false;
+            }
+        }
+    }
+}
+
+// Total size of a section, calculated from the section_length field of PrivateSectionHeader
+fn section_size(bytes: &[u8]) -> DeserializationResult<usize> {
+    let header = try!(PrivateSectionHeader::from_bytes(bytes));
+    Ok(3 + header.section_length as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::ts::TsPacket;
+    use super::*;
+
+    // TDT for 2015-09-01 12:30:00
+    const TDT: [u8; 8] = [0x70, 0x70, 0x05, 0xdf, 0xb2, 0x12, 0x30, 0x00];
+
+    fn packet(continuity_counter: u8, payload_unit_start_indicator: bool, payload: &[u8]) -> TsPacket {
+        let mut payload = payload.to_vec();
+        payload.resize(184, STUFFING_BYTE);
+        TsPacket {
+            transport_error_indicator: false,
+            payload_unit_start_indicator: payload_unit_start_indicator,
+            transport_priority: false,
+            pid: 0x14,
+            transport_scrambling_control: 0,
+            adaptation_field_control: 0b01,
+            continuity_counter: continuity_counter,
+            adaptation_field: None,
+            payload: payload,
+        }
+    }
+
+    // Stuffing table of 300 bytes
+    fn long_section() -> Vec<u8> {
+        let mut section = vec![0x72, 0x71, 0x29];
+        section.extend((0..297).map(|i| i as u8));
+        section
+    }
+
+    #[test]
+    fn sections_in_one_packet() {
+        let mut assembler = SectionAssembler::new(0x14);
+        let mut payload = vec![0x00];
+        payload.extend(TDT.iter().cloned());
+        payload.extend(TDT.iter().cloned());
+        assert_eq!(assembler.push(&packet(0, true, &payload)), vec![TDT.to_vec(), TDT.to_vec()]);
+
+        let mut other_pid = packet(1, true, &payload);
+        other_pid.pid = 0x11;
+        assert!(assembler.push(&other_pid).is_empty());
+    }
+
+    #[test]
+    fn section_spanning_packets() {
+        let section = long_section();
+        let mut assembler = SectionAssembler::new(0x14);
+        let mut payload = vec![0x00];
+        payload.extend(section[..183].iter().cloned());
+        assert!(assembler.push(&packet(7, true, &payload)).is_empty());
+
+        // The rest of the section, followed by the start of a TDT pointed to by pointer_field
+        let mut payload = vec![(section.len() - 183) as u8];
+        payload.extend(section[183..].iter().cloned());
+        payload.extend(TDT.iter().cloned());
+        assert_eq!(assembler.push(&packet(8, true, &payload)), vec![section, TDT.to_vec()]);
+    }
+
+    #[test]
+    fn discontinuity() {
+        let section = long_section();
+        let mut assembler = SectionAssembler::new(0x14);
+        let mut payload = vec![0x00];
+        payload.extend(section[..183].iter().cloned());
+        assembler.push(&packet(7, true, &payload));
+        // A lost packet discards the partial section
+        assert!(assembler.push(&packet(9, false, &section[183..])).is_empty());
+
+        // Sections starting in a packet after the gap are received
+        let mut payload = vec![0x00];
+        payload.extend(TDT.iter().cloned());
+        assert_eq!(assembler.push(&packet(10, true, &payload)), vec![TDT.to_vec()]);
+    }
+}
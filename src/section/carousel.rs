@@ -0,0 +1,290 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Repeating tables for playout
+//!
+//! Typical repetition intervals (ETSI TR 101 211) are 100 ms for PAT and PMT, 2 s for EIT
+//! present/following of the actual transport stream, and up to 10 s for NIT and SDT.
+
+use std::collections::{HashMap,VecDeque};
+use std::num::NonZeroU64;
+use std::time::Duration;
+
+use ::ts::{TsPacket,PACKET_SIZE,NULL_PID};
+use super::encoder::set_version_number;
+use super::packetizer::SectionPacketizer;
+
+/// Identifies a table inserted into a `Carousel`.
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub struct CarouselTableId(usize);
+
+#[derive(Debug,Clone)]
+struct CarouselTable {
+    pid: u16,
+    interval: Duration,
+    sections: Vec<Vec<u8>>,
+    version_number: Option<u8>,
+    next_due: Duration,
+}
+
+/// Repeats encoded tables at their own intervals. Time is either given by the caller with `poll`,
+/// or derived from the bitrate of the output with `next_packet`.
+#[derive(Debug,Clone)]
+pub struct Carousel {
+    tables: HashMap<usize, CarouselTable>,
+    next_id: usize,
+    packetizers: HashMap<u16, SectionPacketizer>,
+    queue: VecDeque<TsPacket>,
+    clock: Duration,
+    // Constant bitrate output of next_packet: its bitrate, the clock when it started and the
+    // number of packets sent since then
+    output: Option<(NonZeroU64, Duration, u64)>,
+}
+
+impl Carousel {
+    pub fn new() -> Carousel {
+        Carousel {
+            tables: HashMap::new(),
+            next_id: 0,
+            packetizers: HashMap::new(),
+            queue: VecDeque::new(),
+            clock: Duration::new(0, 0),
+            output: None,
+        }
+    }
+
+    /// Add a table, given as its encoded sections, to be sent on `pid` every `interval`. The
+    /// table is first sent as soon as possible.
+    pub fn insert(&mut self, pid: u16, interval: Duration, sections: Vec<Vec<u8>>) -> CarouselTableId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.tables.insert(id, CarouselTable {
+            pid: pid,
+            interval: interval,
+            version_number: sections.first().and_then(|section| version_number(section)),
+            sections: sections,
+            next_due: self.clock,
+        });
+        CarouselTableId(id)
+    }
+
+    /// Replace the sections of a table. The version_number of the previous sections is incremented
+    /// and written to the new ones, which are sent as soon as possible. Returns the new
+    /// version_number, or `None` if the table is not in the carousel or has no version_number.
+    pub fn update(&mut self, id: CarouselTableId, mut sections: Vec<Vec<u8>>) -> Option<u8> {
+        let clock = self.clock;
+        let table = match self.tables.get_mut(&id.0) {
+            Some(table) => table,
+            None => return None,
+        };
+        table.version_number = match table.version_number {
+            Some(version) => {
+                let version = (version + 1) & 0x1f;
+                for section in &mut sections {
+                    set_version_number(section, version);
+                }
+                Some(version)
+            },
+            None => sections.first().and_then(|section| version_number(section)),
+        };
+        table.sections = sections;
+        table.next_due = clock;
+        table.version_number
+    }
+
+    pub fn remove(&mut self, id: CarouselTableId) -> bool {
+        self.tables.remove(&id.0).is_some()
+    }
+
+    /// Packets of all tables that are due at `now`, for muxers that keep their own packet clock
+    /// and fill the gaps with other packets. The clock of the carousel never goes backwards, so a
+    /// `now` earlier than in a previous call is taken to be the same time as then.
+    pub fn poll(&mut self, now: Duration) -> Vec<TsPacket> {
+        if now > self.clock {
+            self.clock = now;
+            self.output = None;
+        }
+        for id in self.due_tables() {
+            self.queue_table(id);
+        }
+        self.queue.drain(..).collect()
+    }
+
+    /// The next packet of an output with the given bitrate (bits per second). Null packets are
+    /// sent when no table is due. Each call advances the clock of the carousel by the duration of
+    /// one packet.
+    pub fn next_packet(&mut self, bitrate: NonZeroU64) -> TsPacket {
+        if self.queue.is_empty() {
+            if let Some(&id) = self.due_tables().first() {
+                self.queue_table(id);
+            }
+        }
+        let packet = self.queue.pop_front().unwrap_or_else(null_packet);
+        // The clock is derived from the packet count, so that the rounding of the duration of a
+        // packet does not accumulate
+        let (start, packets) = match self.output {
+            Some((output_bitrate, start, packets)) if output_bitrate == bitrate => (start, packets + 1),
+            _ => (self.clock, 1),
+        };
+        self.output = Some((bitrate, start, packets));
+        self.clock = start + packets_duration(packets, bitrate);
+        packet
+    }
+
+    // Tables that are due, the one that has been due for the longest time first
+    fn due_tables(&self) -> Vec<usize> {
+        let mut due: Vec<(Duration, usize)> = self.tables.iter()
+            .filter(|&(_, table)| table.next_due <= self.clock)
+            .map(|(&id, table)| (table.next_due, id))
+            .collect();
+        due.sort();
+        due.into_iter().map(|(_, id)| id).collect()
+    }
+
+    fn queue_table(&mut self, id: usize) {
+        let clock = self.clock;
+        let table = match self.tables.get_mut(&id) {
+            Some(table) => table,
+            None => return,
+        };
+        let pid = table.pid;
+        let packetizer = self.packetizers.entry(pid).or_insert_with(|| SectionPacketizer::new(pid));
+        self.queue.extend(packetizer.packetize(&table.sections));
+        table.next_due = table.next_due + table.interval;
+        if table.next_due <= clock {
+            // Far behind schedule, don't try to catch up with a burst of repetitions
+            table.next_due = clock + table.interval;
+        }
+    }
+}
+
+fn version_number(section: &[u8]) -> Option<u8> {
+    if section.len() < 6 || section[1] & 0x80 == 0 {
+        return None;
+    }
+    Some(section[5] >> 1 & 0x1f)
+}
+
+fn packets_duration(packets: u64, bitrate: NonZeroU64) -> Duration {
+    let bits = packets as u128 * PACKET_SIZE as u128 * 8;
+    let bitrate = bitrate.get() as u128;
+    let nanoseconds = (bits % bitrate) * 1_000_000_000 / bitrate;
+    Duration::new((bits / bitrate) as u64, nanoseconds as u32)
+}
+
+fn null_packet() -> TsPacket {
+    TsPacket {
+        transport_error_indicator: false,
+        payload_unit_start_indicator: false,
+        transport_priority: false,
+        pid: NULL_PID,
+        transport_scrambling_control: 0,
+        adaptation_field_control: 0b01,
+        continuity_counter: 0,
+        adaptation_field: None,
+        payload: vec![0xff; PACKET_SIZE - 4],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+    use std::time::Duration;
+    use ::base::*;
+    use ::ts::NULL_PID;
+    use super::*;
+    use super::super::assembler::SectionAssembler;
+    use super::super::psi::ProgramAssociationSection;
+    use ::fixtures::PAT;
+
+    fn milliseconds(milliseconds: u64) -> Duration {
+        Duration::from_millis(milliseconds)
+    }
+
+    #[test]
+    fn repetition() {
+        let mut carousel = Carousel::new();
+        carousel.insert(0x00, milliseconds(100), vec![PAT.to_vec()]);
+        let packets = carousel.poll(milliseconds(0));
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].pid, 0x00);
+        assert!(carousel.poll(milliseconds(50)).is_empty());
+        let packets = carousel.poll(milliseconds(100));
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].continuity_counter, 1);
+        // Repetitions missed while far behind are not sent in a burst
+        assert_eq!(carousel.poll(milliseconds(1000)).len(), 1);
+        assert!(carousel.poll(milliseconds(1050)).is_empty());
+        assert_eq!(carousel.poll(milliseconds(1100)).len(), 1);
+    }
+
+    #[test]
+    fn poll_earlier_time() {
+        let mut carousel = Carousel::new();
+        carousel.insert(0x00, milliseconds(1000), vec![PAT.to_vec()]);
+        assert_eq!(carousel.poll(milliseconds(1000)).len(), 1);
+        // The clock stays at 1000 ms, when the new table is first sent
+        assert!(carousel.poll(milliseconds(0)).is_empty());
+        carousel.insert(0x10, milliseconds(100), vec![PAT.to_vec()]);
+        assert_eq!(carousel.poll(milliseconds(0)).len(), 1);
+        assert!(carousel.poll(milliseconds(1000)).is_empty());
+        assert_eq!(carousel.poll(milliseconds(1100)).len(), 1);
+    }
+
+    #[test]
+    fn update_and_remove() {
+        let mut carousel = Carousel::new();
+        let id = carousel.insert(0x00, milliseconds(1000), vec![PAT.to_vec()]);
+        let mut assembler = SectionAssembler::new(0x00);
+        for packet in carousel.poll(milliseconds(0)) {
+            assembler.push(&packet);
+        }
+
+        // The updated table is sent immediately with the next version_number
+        assert_eq!(carousel.update(id, vec![PAT.to_vec()]), Some(1));
+        let sections: Vec<Vec<u8>> = carousel.poll(milliseconds(10)).iter()
+            .flat_map(|packet| assembler.push(packet))
+            .collect();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(ProgramAssociationSection::from_bytes(&sections[0]).unwrap().version_number, 1);
+
+        assert!(carousel.remove(id));
+        assert!(!carousel.remove(id));
+        assert_eq!(carousel.update(id, vec![PAT.to_vec()]), None);
+        assert!(carousel.poll(milliseconds(2000)).is_empty());
+    }
+
+    #[test]
+    fn constant_bitrate() {
+        // One packet lasts 100 ms at 15040 bits per second
+        let bitrate = NonZeroU64::new(15040).unwrap();
+        let mut carousel = Carousel::new();
+        carousel.insert(0x00, milliseconds(1000), vec![PAT.to_vec()]);
+        let pids: Vec<u16> = (0..12).map(|_| carousel.next_packet(bitrate).pid).collect();
+        let mut expected = vec![0x00];
+        expected.extend(vec![NULL_PID; 9]);
+        expected.extend(vec![0x00, NULL_PID]);
+        assert_eq!(pids, expected);
+    }
+
+    #[test]
+    fn bitrate_rounding() {
+        // One packet lasts 501.33 ms at 3000 bits per second, so three packets last exactly 1504 ms
+        let bitrate = NonZeroU64::new(3000).unwrap();
+        let mut carousel = Carousel::new();
+        carousel.insert(0x00, milliseconds(1504), vec![PAT.to_vec()]);
+        let pids: Vec<u16> = (0..5).map(|_| carousel.next_packet(bitrate).pid).collect();
+        assert_eq!(pids, vec![0x00, NULL_PID, NULL_PID, 0x00, NULL_PID]);
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Encoding complete sections and splitting tables into sections
+
+use ::base::*;
+use bitwriter::BitWriter;
+use super::PrivateSectionHeader;
+
+// table_id, section_syntax_indicator, private_indicator, reserved bits and section_length
+const SECTION_INTRO_BYTES: usize = 3;
+
+/// Largest section_length allowed for sections of a table. PSI and DVB SI tables other than EIT
+/// are limited to 1021 bytes, EIT and private sections to 4093 bytes.
+pub fn max_section_length(table_id: u8) -> usize {
+    match table_id {
+        0x00...0x03 => 1021,
+        0x4e...0x6f => 4093,
+        0x40...0x7f => 1021,
+        _ => 4093,
+    }
+}
+
+/// Encodes a section from a header and its payload. The section_length of the header is ignored
+/// and computed from the payload, and long form sections get their CRC_32 appended.
+pub fn encode_section(header: &PrivateSectionHeader, payload: &[u8]) -> SerializationResult<Vec<u8>> {
+    let mut header = *header;
+    let crc_length = if header.extended_header.is_some() { 4 } else { 0 };
+    let extended_header_length = if header.extended_header.is_some() { 5 } else { 0 };
+    let section_length = extended_header_length + payload.len() + crc_length;
+    try!(check_section_length(header.table_id, section_length));
+    header.section_length = section_length as u16;
+
+    let mut writer = BitWriter::new();
+    try!(header.serialize(&mut writer));
+    try!(writer.write_bytes(payload));
+    if header.extended_header.is_some() {
+        try!(write_crc(&mut writer));
+    }
+    Ok(writer.into_bytes())
+}
+
+/// Encodes a table whose items do not necessarily fit in a single section. The items are packed
+/// into as few sections as possible, in order, and `build` is called for each section with its
+/// items, section_number and last_section_number. `build` is also called with no items to find out
+/// the size of the rest of each section.
+///
+/// Segmenting of EIT schedule tables is left to the caller.
+pub fn encode_table<T, S, F>(items: Vec<T>, mut build: F) -> SerializationResult<Vec<Vec<u8>>>
+    where T: Serialize, S: Serialize, F: FnMut(Vec<T>, u8, u8) -> S
+{
+    let mut groups: Vec<Vec<T>> = vec![];
+    let mut current: Vec<T> = vec![];
+    let mut current_length = try!(empty_section_length(&mut build, 0));
+    let mut max_length = current_length.1;
+    for item in items {
+        let item_length = try!(serialized_length(&item)) as usize;
+        if current_length.0 + item_length > max_length && !current.is_empty() {
+            groups.push(current);
+            current = vec![];
+            if groups.len() > 0xff {
+                return Err(SerializationError::InvalidValue("table does not fit in 256 sections"));
+            }
+            current_length = try!(empty_section_length(&mut build, groups.len() as u8));
+            max_length = current_length.1;
+        }
+        if current_length.0 + item_length > max_length {
+            return Err(SerializationError::SectionTooLong {
+                length: current_length.0 + item_length,
+                max_length: max_length,
+            });
+        }
+        current_length.0 += item_length;
+        current.push(item);
+    }
+    groups.push(current);
+
+    let last_section_number = (groups.len() - 1) as u8;
+    let mut sections = vec![];
+    for (section_number, group) in groups.into_iter().enumerate() {
+        let section = try!(build(group, section_number as u8, last_section_number).to_bytes());
+        try!(check_section_length(section[0], section.len() - SECTION_INTRO_BYTES));
+        sections.push(section);
+    }
+    Ok(sections)
+}
+
+/// Changes the version_number of an encoded long form section and updates its CRC_32. Short form
+/// sections have no version_number and are left unchanged.
+pub fn set_version_number(section: &mut [u8], version_number: u8) {
+    // Intro, extended header and CRC_32
+    if section.len() < SECTION_INTRO_BYTES + 5 + 4 || section[1] & 0x80 == 0 {
+        return;
+    }
+    section[5] = section[5] & 0xc1 | (version_number & 0x1f) << 1;
+    let crc_position = section.len() - 4;
+    let crc = ::crc::crc32_mpeg2(&section[..crc_position]);
+    for (i, byte) in section[crc_position..].iter_mut().enumerate() {
+        *byte = (crc >> (24 - 8 * i)) as u8;
+    }
+}
+
+// section_length of a section without items, and the largest allowed section_length
+fn empty_section_length<T, S, F>(build: &mut F, section_number: u8) -> SerializationResult<(usize, usize)>
+    where S: Serialize, F: FnMut(Vec<T>, u8, u8) -> S
+{
+    let section = try!(build(vec![], section_number, section_number).to_bytes());
+    Ok((section.len() - SECTION_INTRO_BYTES, max_section_length(section[0])))
+}
+
+fn check_section_length(table_id: u8, section_length: usize) -> SerializationResult<()> {
+    let max_length = max_section_length(table_id);
+    if section_length > max_length {
+        return Err(SerializationError::SectionTooLong {
+            length: section_length,
+            max_length: max_length,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::base::*;
+    use super::*;
+    use super::super::ExtendedPrivateSectionHeader;
+    use super::super::psi::{ProgramAssociation,ProgramAssociationSection};
+    use ::fixtures::PAT;
+
+    fn pat_header() -> PrivateSectionHeader {
+        PrivateSectionHeader {
+            table_id: 0x00,
+            private_indicator: 0,
+            // Computed by the encoder
+            section_length: 0,
+            extended_header: Some(ExtendedPrivateSectionHeader {
+                table_id_extension: 1,
+                version_number: 0,
+                current_next_indicator: true,
+                section_number: 0,
+                last_section_number: 0,
+            }),
+        }
+    }
+
+    fn associations(count: u16) -> Vec<ProgramAssociation> {
+        (1..count + 1).map(|program_number| ProgramAssociation::ProgramMapPid {
+            program_number: program_number,
+            program_map_pid: 0x100 + program_number,
+        }).collect()
+    }
+
+    #[test]
+    fn long_section() {
+        assert_eq!(encode_section(&pat_header(), &[0x00, 0x01, 0xe1, 0x00]).unwrap(), PAT.to_vec());
+        match encode_section(&pat_header(), &[0; 1013]) {
+            Err(SerializationError::SectionTooLong { length: 1022, max_length: 1021 }) => {},
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn short_section() {
+        let header = PrivateSectionHeader {
+            table_id: 0x70,
+            private_indicator: 1,
+            section_length: 0,
+            extended_header: None,
+        };
+        assert_eq!(encode_section(&header, &[0xdf, 0xb2, 0x12, 0x30, 0x00]).unwrap(),
+                   vec![0x70, 0x70, 0x05, 0xdf, 0xb2, 0x12, 0x30, 0x00]);
+    }
+
+    #[test]
+    fn table_splitting() {
+        // 253 associations of four bytes fill a PAT section of 1021 bytes
+        let sections = encode_table(associations(300), |associations, section_number, last_section_number| {
+            ProgramAssociationSection {
+                transport_stream_id: 1,
+                version_number: 3,
+                current_next_indicator: true,
+                section_number: section_number,
+                last_section_number: last_section_number,
+                associations: associations,
+            }
+        }).unwrap();
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].len(), 3 + 1021);
+        let first = ProgramAssociationSection::from_bytes(&sections[0]).unwrap();
+        let second = ProgramAssociationSection::from_bytes(&sections[1]).unwrap();
+        assert_eq!((first.section_number, first.last_section_number), (0, 1));
+        assert_eq!((second.section_number, second.last_section_number), (1, 1));
+        assert_eq!(first.associations.len(), 253);
+        assert_eq!(second.associations.len(), 47);
+    }
+
+    #[test]
+    fn version_number() {
+        let mut section = PAT.to_vec();
+        set_version_number(&mut section, 5);
+        let pat = ProgramAssociationSection::from_bytes(&section).unwrap();
+        assert_eq!(pat.version_number, 5);
+        assert!(pat.current_next_indicator);
+
+        let mut tdt = vec![0x70, 0x70, 0x05, 0xdf, 0xb2, 0x12, 0x30, 0x00];
+        set_version_number(&mut tdt, 5);
+        assert_eq!(tdt, vec![0x70, 0x70, 0x05, 0xdf, 0xb2, 0x12, 0x30, 0x00]);
+    }
+
+    #[test]
+    fn max_lengths() {
+        assert_eq!(max_section_length(0x00), 1021);
+        assert_eq!(max_section_length(0x42), 1021);
+        assert_eq!(max_section_length(0x50), 4093);
+        assert_eq!(max_section_length(0x80), 4093);
+    }
+}
@@ -12,10 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ::base::Deserialize;
+use ::base::*;
 use bitreader::BitReader;
+use bitwriter::BitWriter;
 
 pub mod psi;
+pub mod si;
+pub mod assembler;
+pub mod packetizer;
+pub mod carousel;
+pub mod table;
+pub mod encoder;
 
 bit_struct!(
     #[derive(Debug,Copy,Clone)]
@@ -27,7 +34,7 @@ bit_struct!(
     }
     deserialize(reader) {
         table_id: { 8 },
-        section_syntax_indicator: { 1, type: u8 },
+        section_syntax_indicator: { 1, type: u8, write: extended_header.is_some() },
         private_indicator: { 1 },
         reserved: { 2 },
         section_length: { 12 },
@@ -60,13 +67,228 @@ bit_struct!(
     }
 );
 
-fn bits_remaining(section_length: u16, reader: &BitReader) -> u64 {
+#[derive(Debug)]
+pub enum Section {
+    ProgramAssociation(psi::ProgramAssociationSection),
+    ConditionalAccess(psi::ConditionalAccessSection),
+    ProgramMap(psi::ProgramMapSection),
+    TransportStreamDescription(psi::TransportStreamDescriptionSection),
+    NetworkInformation(si::NetworkInformationSection),
+    ServiceDescription(si::ServiceDescriptionSection),
+    BouquetAssociation(si::BouquetAssociationSection),
+    EventInformation(si::EventInformationSection),
+    TimeDate(si::TimeDateSection),
+    TimeOffset(si::TimeOffsetSection),
+    RunningStatus(si::RunningStatusSection),
+    Stuffing(si::StuffingSection),
+    DiscontinuityInformation(si::DiscontinuityInformationSection),
+    SelectionInformation(si::SelectionInformationSection),
+    Unknown {
+        header: PrivateSectionHeader,
+        payload: Vec<u8>,
+    },
+}
+
+impl Deserialize for Section {
+    fn deserialize(reader: &mut BitReader) -> DeserializationResult<Section> {
+        deserialize_section(reader)
+    }
+
+    fn deserialize_with_options(reader: &mut BitReader, options: &DeserializeOptions) -> DeserializationResult<Section> {
+        deserialize_section_with_options(reader, options)
+    }
+}
+
+impl Serialize for Section {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()> {
+        match *self {
+            Section::ProgramAssociation(ref section) => section.serialize(writer),
+            Section::ConditionalAccess(ref section) => section.serialize(writer),
+            Section::ProgramMap(ref section) => section.serialize(writer),
+            Section::TransportStreamDescription(ref section) => section.serialize(writer),
+            Section::NetworkInformation(ref section) => section.serialize(writer),
+            Section::ServiceDescription(ref section) => section.serialize(writer),
+            Section::BouquetAssociation(ref section) => section.serialize(writer),
+            Section::EventInformation(ref section) => section.serialize(writer),
+            Section::TimeDate(ref section) => section.serialize(writer),
+            Section::TimeOffset(ref section) => section.serialize(writer),
+            Section::RunningStatus(ref section) => section.serialize(writer),
+            Section::Stuffing(ref section) => section.serialize(writer),
+            Section::DiscontinuityInformation(ref section) => section.serialize(writer),
+            Section::SelectionInformation(ref section) => section.serialize(writer),
+            Section::Unknown { ref header, ref payload } => {
+                let section = try!(encoder::encode_section(header, payload));
+                Ok(try!(writer.write_bytes(&section)))
+            },
+        }
+    }
+}
+
+macro_rules! section_match {
+    (
+        match $table_id:ident with $reader:ident, $options:ident {
+            $($($id:pat)|+ => $variant:ident),+
+        }
+    ) => (
+        match $table_id {
+            $(
+                $($id)|+ => Ok(Section::$variant(try!(Deserialize::deserialize_with_options($reader, $options))))
+            ),+,
+            _ => deserialize_unknown_section($reader, $options)
+        }
+    );
+}
+
+pub fn deserialize_section(reader: &mut BitReader) -> DeserializationResult<Section> {
+    deserialize_section_with_options(reader, &DeserializeOptions::new())
+}
+
+pub fn deserialize_section_with_options(reader: &mut BitReader, options: &DeserializeOptions) -> DeserializationResult<Section> {
+    let mut table_id_reader = reader.relative_reader();
+    let table_id = try!(table_id_reader.read_u8(8));
+    section_match!(
+        match table_id with reader, options {
+            0x00 => ProgramAssociation,
+            0x01 => ConditionalAccess,
+            0x02 => ProgramMap,
+            0x03 => TransportStreamDescription,
+            0x40 | 0x41 => NetworkInformation,
+            0x42 | 0x46 => ServiceDescription,
+            0x4a => BouquetAssociation,
+            0x4e...0x6f => EventInformation,
+            0x70 => TimeDate,
+            0x71 => RunningStatus,
+            0x72 => Stuffing,
+            0x73 => TimeOffset,
+            0x7e => DiscontinuityInformation,
+            0x7f => SelectionInformation
+        }
+    )
+}
+
+fn deserialize_unknown_section(original_reader: &mut BitReader, options: &DeserializeOptions) -> DeserializationResult<Section> {
+    let mut reader = original_reader.relative_reader();
+    let header: PrivateSectionHeader = try!(Deserialize::deserialize(&mut reader));
+    let crc_bits = if header.extended_header.is_some() { 32 } else { 0 };
+    let section_bits = (3 + header.section_length as u64) * 8;
+    if reader.position() + crc_bits > section_bits {
+        return Err(DeserializationError::ReadTooMuch {
+            position: reader.position() + crc_bits,
+            max_position: section_bits,
+        });
+    }
+    let mut payload = vec![];
+    while reader.position() + crc_bits < section_bits {
+        payload.push(try!(reader.read_u8(8)));
+    }
+    if header.extended_header.is_some() {
+        try!(verify_crc(original_reader.relative_reader(), &mut reader, options));
+    }
+    try!(original_reader.skip(reader.position()));
+    Ok(Section::Unknown {
+        header: header,
+        payload: payload,
+    })
+}
+
+fn bits_remaining(section_length: u16, reader: &BitReader) -> DeserializationResult<u64> {
+    let crc_length = 32;
+    section_bits_remaining(section_length, crc_length, reader)
+}
+
+// Same as bits_remaining, but for short form sections that have no CRC
+fn short_section_bits_remaining(section_length: u16, reader: &BitReader) -> DeserializationResult<u64> {
+    section_bits_remaining(section_length, 0, reader)
+}
+
+fn section_bits_remaining(section_length: u16, trailer_bits: u64, reader: &BitReader) -> DeserializationResult<u64> {
     // table_id (8 bits) + section_syntax_indicator (1 bit) +
     // private_indicator (1 bit) + reserved (2 bits) + section_length (12 bits)
     let intro_bits = 24;
-    let crc_length = 32;
-    // How many data bits after intro bits - excluding CRC32
+    // How many data bits after intro bits - excluding the trailer
     let data_bits = section_length as u64 * 8;
     let total_section_bits = intro_bits + data_bits;
-    total_section_bits - reader.position() as u64 - crc_length
+    let position = reader.position() + trailer_bits;
+    if position > total_section_bits {
+        return Err(DeserializationError::ReadTooMuch {
+            position: position,
+            max_position: total_section_bits,
+        });
+    }
+    Ok(total_section_bits - position)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::base::*;
+    use super::*;
+    use ::fixtures::PAT;
+
+    // User private table 0x80 with a two byte payload
+    const PRIVATE: [u8; 14] = [0x80, 0xb0, 0x0b, 0x12, 0x34, 0xc3, 0x00, 0x00, 0xaa, 0xbb, 0x3b,
+        0x60, 0x2e, 0x49];
+
+    #[test]
+    fn known_table() {
+        let mut reader = BitReader::new(&PAT);
+        match deserialize_section(&mut reader).unwrap() {
+            Section::ProgramAssociation(ref pat) => assert_eq!(pat.transport_stream_id, 1),
+            section => panic!("unexpected section {:?}", section),
+        }
+        assert_eq!(reader.position(), PAT.len() as u64 * 8);
+
+        let tdt = [0x70, 0x70, 0x05, 0xdf, 0xb2, 0x12, 0x30, 0x00];
+        match Section::from_bytes(&tdt).unwrap() {
+            Section::TimeDate(..) => {},
+            section => panic!("unexpected section {:?}", section),
+        }
+    }
+
+    #[test]
+    fn unknown_table() {
+        let section = Section::from_bytes(&PRIVATE).unwrap();
+        match section {
+            Section::Unknown { ref header, ref payload } => {
+                assert_eq!(header.table_id, 0x80);
+                let extended_header = header.extended_header.unwrap();
+                assert_eq!(extended_header.table_id_extension, 0x1234);
+                assert_eq!(extended_header.version_number, 1);
+                assert_eq!(*payload, vec![0xaa, 0xbb]);
+            },
+            ref section => panic!("unexpected section {:?}", section),
+        }
+        assert_eq!(section.to_bytes().unwrap(), PRIVATE.to_vec());
+
+        let short = [0x90, 0x70, 0x02, 0x01, 0x02];
+        match Section::from_bytes(&short).unwrap() {
+            Section::Unknown { ref header, ref payload } => {
+                assert!(header.extended_header.is_none());
+                assert_eq!(*payload, vec![0x01, 0x02]);
+            },
+            section => panic!("unexpected section {:?}", section),
+        }
+    }
+
+    #[test]
+    fn unknown_table_crc_mismatch() {
+        let mut bytes = PRIVATE.to_vec();
+        bytes[8] = 0x00;
+        assert!(Section::from_bytes(&bytes).is_err());
+        let options = DeserializeOptions { verify_crc: false };
+        assert!(Section::from_bytes_with_options(&bytes, &options).is_ok());
+    }
+
+    #[test]
+    fn section_length_too_short() {
+        // SDT whose section_length ends before the extended header
+        let mut sdt = [0x42, 0xf0, 0x03, 0x00, 0x01, 0xc1, 0x00, 0x00, 0x00, 0x01, 0xff, 0x00, 0x00,
+            0x00, 0x00];
+        for &section_length in &[0x03, 0x08] {
+            sdt[2] = section_length;
+            match Section::from_bytes(&sdt) {
+                Err(DeserializationError::ReadTooMuch { .. }) => {},
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+    }
 }
@@ -0,0 +1,191 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Packetizing sections into transport stream packets
+
+use ::ts::{TsPacket,PACKET_SIZE};
+
+const STUFFING_BYTE: u8 = 0xff;
+const PAYLOAD_SIZE: usize = PACKET_SIZE - 4;
+
+/// Splits the sections of a single PID into TS packets, the inverse of `SectionAssembler`.
+#[derive(Debug,Clone)]
+pub struct SectionPacketizer {
+    pid: u16,
+    continuity_counter: u8,
+    pack_sections: bool,
+}
+
+impl SectionPacketizer {
+    pub fn new(pid: u16) -> SectionPacketizer {
+        SectionPacketizer {
+            pid: pid,
+            continuity_counter: 0,
+            pack_sections: true,
+        }
+    }
+
+    pub fn pid(&self) -> u16 {
+        self.pid
+    }
+
+    /// The continuity counter of the next packet.
+    pub fn continuity_counter(&self) -> u8 {
+        self.continuity_counter
+    }
+
+    /// By default a section may start in the same packet where the previous one ends. When
+    /// packing is disabled, every section starts a new packet.
+    pub fn pack_sections(&mut self, pack: bool) {
+        self.pack_sections = pack;
+    }
+
+    /// Packetize sections, in order. The packet carrying the end of the last section is padded
+    /// with stuffing bytes, so nothing is left over for the next call.
+    pub fn packetize(&mut self, sections: &[Vec<u8>]) -> Vec<TsPacket> {
+        let mut packets = vec![];
+        if self.pack_sections {
+            let data: Vec<u8> = sections.iter().flat_map(|section| section.iter().cloned()).collect();
+            let mut starts = vec![];
+            let mut offset = 0;
+            for section in sections {
+                starts.push(offset);
+                offset += section.len();
+            }
+            self.packetize_data(&data, &starts, &mut packets);
+        } else {
+            for section in sections {
+                self.packetize_data(section, &[0], &mut packets);
+            }
+        }
+        packets
+    }
+
+    fn packetize_data(&mut self, data: &[u8], starts: &[usize], packets: &mut Vec<TsPacket>) {
+        let mut position = 0;
+        let mut starts = starts.iter().cloned().peekable();
+        while position < data.len() {
+            let mut payload = Vec::with_capacity(PAYLOAD_SIZE);
+            let next_start = starts.peek().cloned();
+            // pointer_field takes one byte of the payload
+            let pointer_field = match next_start {
+                Some(start) if start < position + PAYLOAD_SIZE - 1 => Some(start - position),
+                _ => None,
+            };
+            let mut end = ::std::cmp::min(data.len(), position + PAYLOAD_SIZE);
+            match pointer_field {
+                Some(pointer_field) => {
+                    payload.push(pointer_field as u8);
+                    end = ::std::cmp::min(end, position + PAYLOAD_SIZE - 1);
+                },
+                // A section can not start in the last byte of a packet without a pointer_field
+                None => if let Some(start) = next_start {
+                    end = ::std::cmp::min(end, start);
+                },
+            }
+            while starts.peek().map_or(false, |&start| start < end) {
+                starts.next();
+            }
+            payload.extend(data[position..end].iter().cloned());
+            position = end;
+            while payload.len() < PAYLOAD_SIZE {
+                payload.push(STUFFING_BYTE);
+            }
+            packets.push(self.packet(pointer_field.is_some(), payload));
+        }
+    }
+
+    fn packet(&mut self, payload_unit_start_indicator: bool, payload: Vec<u8>) -> TsPacket {
+        let continuity_counter = self.continuity_counter;
+        self.continuity_counter = (self.continuity_counter + 1) & 0xf;
+        TsPacket {
+            transport_error_indicator: false,
+            payload_unit_start_indicator: payload_unit_start_indicator,
+            transport_priority: false,
+            pid: self.pid,
+            transport_scrambling_control: 0,
+            adaptation_field_control: 0b01,
+            continuity_counter: continuity_counter,
+            adaptation_field: None,
+            payload: payload,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::assembler::SectionAssembler;
+
+    // Stuffing section of the given total size
+    fn section(size: usize) -> Vec<u8> {
+        let section_length = size - 3;
+        let mut section = vec![0x72, 0x70 | (section_length >> 8) as u8, section_length as u8];
+        section.extend((0..section_length).map(|i| i as u8));
+        section
+    }
+
+    fn round_trip(packetizer: &mut SectionPacketizer, sections: &[Vec<u8>]) -> Vec<TsPacket> {
+        let packets = packetizer.packetize(sections);
+        let mut assembler = SectionAssembler::new(packetizer.pid());
+        let mut assembled = vec![];
+        for packet in &packets {
+            assert_eq!(packet.payload.len(), PAYLOAD_SIZE);
+            assembled.extend(assembler.push(packet));
+        }
+        assert_eq!(assembled, sections.to_vec());
+        packets
+    }
+
+    #[test]
+    fn packed_sections() {
+        let mut packetizer = SectionPacketizer::new(0x11);
+        let packets = round_trip(&mut packetizer, &[section(8), section(8)]);
+        assert_eq!(packets.len(), 1);
+        assert!(packets[0].payload_unit_start_indicator);
+        assert_eq!(packets[0].payload[0], 0);
+        assert_eq!(packets[0].payload[17..], [STUFFING_BYTE; PAYLOAD_SIZE - 17][..]);
+
+        // A section starting in the middle of the second packet is pointed to by its pointer_field
+        let packets = round_trip(&mut packetizer, &[section(300), section(8)]);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1].payload[0] as usize, 300 - (PAYLOAD_SIZE - 1));
+    }
+
+    #[test]
+    fn section_boundaries() {
+        // Sections ending at and around the end of a packet
+        let sections: Vec<Vec<u8>> = (178..190).chain(360..372).map(section).collect();
+        round_trip(&mut SectionPacketizer::new(0x11), &sections);
+
+        let mut packetizer = SectionPacketizer::new(0x11);
+        packetizer.pack_sections(false);
+        let packets = round_trip(&mut packetizer, &sections);
+        assert!(packets.len() > sections.len());
+        assert_eq!(packets.iter().filter(|packet| packet.payload_unit_start_indicator).count(), sections.len());
+    }
+
+    #[test]
+    fn continuity_counter() {
+        let mut packetizer = SectionPacketizer::new(0x11);
+        let packets = packetizer.packetize(&[section(4000)]);
+        assert_eq!(packets.len(), 22);
+        for (i, packet) in packets.iter().enumerate() {
+            assert_eq!(packet.pid, 0x11);
+            assert_eq!(packet.continuity_counter as usize, i % 16);
+            assert_eq!(packet.payload_unit_start_indicator, i == 0);
+        }
+        assert_eq!(packetizer.continuity_counter(), 6);
+    }
+}
@@ -15,8 +15,10 @@
 //! # Program Specific Information (PSI)
 
 use ::base::*;
+use bitwriter::BitWriter;
 use super::bits_remaining;
 use ::descriptor::{Descriptor,deserialize_descriptor};
+use ::component::{ComponentKind,StreamType,classify_component};
 
 bit_struct!(
     #[derive(Debug,Clone)]
@@ -33,7 +35,7 @@ bit_struct!(
         expect: { bits: 1, reference: 1 }, // section_syntax_indicator
         expect: { bits: 1, reference: 0 }, // just a constant zero bit
         reserved: { 2 },
-        section_length: { 12, type: u16 },
+        section_length: { 12, type: u16, length_of: rest },
         transport_stream_id: { 16 },
         reserved: { 2 },
         version_number: { 5 },
@@ -43,12 +45,12 @@ bit_struct!(
         associations: { value: {
             let association_size = 32; // How many bits for single program association
             let mut associations = vec![];
-            while bits_remaining(section_length, reader) >= association_size {
+            while try!(bits_remaining(section_length, reader)) >= association_size {
                 associations.push(try!(Deserialize::deserialize(reader)));
             }
             associations
         } },
-        skip: { bits_remaining(section_length, reader) },
+        skip: { try!(bits_remaining(section_length, reader)) },
         crc: { 32 }
     }
 );
@@ -81,6 +83,18 @@ impl Deserialize for ProgramAssociation {
     }
 }
 
+impl Serialize for ProgramAssociation {
+    fn serialize(&self, writer: &mut BitWriter) -> SerializationResult<()> {
+        let (program_number, pid) = match *self {
+            ProgramAssociation::NetworkPid(pid) => (0, pid),
+            ProgramAssociation::ProgramMapPid { program_number, program_map_pid } => (program_number, program_map_pid),
+        };
+        try!(writer.write_u16(16, program_number));
+        try!(write_reserved(writer, 3));
+        Ok(try!(writer.write_u16(13, pid)))
+    }
+}
+
 
 bit_struct!(
     #[derive(Debug)]
@@ -96,7 +110,40 @@ bit_struct!(
         expect: { bits: 1, reference: 1 }, // section_syntax_indicator
         expect: { bits: 1, reference: 0 }, // just a constant zero bit
         reserved: { 2 },
-        section_length: { 12, type: u16 },
+        section_length: { 12, type: u16, length_of: rest },
+        reserved: { 18 },
+        version_number: { 5 },
+        current_next_indicator: { 1, map: bool_flag },
+        section_number: { 8 },
+        last_section_number: { 8 },
+        descriptors: { value: {
+            let mut descriptors = vec![];
+            while try!(bits_remaining(section_length, reader)) > 0 {
+                descriptors.push(try!(deserialize_descriptor(reader)));
+            }
+            descriptors
+        } },
+        skip: { try!(bits_remaining(section_length, reader)) },
+        crc: { 32 }
+    }
+);
+
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct TransportStreamDescriptionSection {
+        pub version_number: u8,
+        pub current_next_indicator: bool,
+        pub section_number: u8,
+        pub last_section_number: u8,
+        pub descriptors: Vec<Box<Descriptor>>
+    }
+    deserialize(reader) {
+        expect: { bits: 8, reference: 3 }, // table_id
+        expect: { bits: 1, reference: 1 }, // section_syntax_indicator
+        expect: { bits: 1, reference: 0 }, // just a constant zero bit
+        reserved: { 2 },
+        section_length: { 12, type: u16, length_of: rest },
         reserved: { 18 },
         version_number: { 5 },
         current_next_indicator: { 1, map: bool_flag },
@@ -104,12 +151,12 @@ bit_struct!(
         last_section_number: { 8 },
         descriptors: { value: {
             let mut descriptors = vec![];
-            while bits_remaining(section_length, reader) > 0 {
+            while try!(bits_remaining(section_length, reader)) > 0 {
                 descriptors.push(try!(deserialize_descriptor(reader)));
             }
             descriptors
         } },
-        skip: { bits_remaining(section_length, reader) },
+        skip: { try!(bits_remaining(section_length, reader)) },
         crc: { 32 }
     }
 );
@@ -127,7 +174,7 @@ bit_struct!(
         reserved: { 3 },
         elementary_pid: { 13 },
         reserved: { 4 },
-        es_info_length: { 12, type: u64 },
+        es_info_length: { 12, type: u64, length_of: es_info },
         es_info: { value: {
             let mut info = vec![];
             let mut bits_remaining = es_info_length * 8;
@@ -142,6 +189,17 @@ bit_struct!(
     }
 );
 
+impl ElementaryStreamInfo {
+    pub fn stream_type(&self) -> StreamType {
+        StreamType::from(self.stream_type)
+    }
+
+    /// What the stream carries, from its stream_type and descriptors.
+    pub fn kind(&self) -> ComponentKind {
+        classify_component(self.stream_type(), &self.es_info)
+    }
+}
+
 bit_struct!(
     #[derive(Debug)]
     pub struct ProgramMapSection {
@@ -159,7 +217,7 @@ bit_struct!(
         expect: { bits: 1, reference: 1 }, // section_syntax_indicator
         expect: { bits: 1, reference: 0 }, // 0
         reserved: { 2 },
-        section_length: { 12, type: u16 },
+        section_length: { 12, type: u16, length_of: rest },
         program_number: { 16 },
         reserved: { 2 },
         version_number: { 5 },
@@ -169,7 +227,7 @@ bit_struct!(
         reserved: { 3 },
         pcr_pid: { 13 },
         reserved: { 4 },
-        program_info_length: { 12, type: u64 },
+        program_info_length: { 12, type: u64, length_of: descriptors },
         descriptors: { value: {
             let mut descriptors = vec![];
             let mut bits_remaining = program_info_length * 8;
@@ -183,12 +241,61 @@ bit_struct!(
         } },
         programs: { value: {
             let mut programs = vec![];
-            while bits_remaining(section_length, reader) > 0 {
+            while try!(bits_remaining(section_length, reader)) > 0 {
                 programs.push(try!(Deserialize::deserialize(reader)));
             }
             programs
         }},
-        skip: { bits_remaining(section_length, reader) },
+        skip: { try!(bits_remaining(section_length, reader)) },
         crc: { 32 }
     }
 );
+
+#[cfg(test)]
+mod tests {
+    use ::base::*;
+    use ::descriptor::find_descriptor;
+    use ::descriptor::dvb::TransportStreamDescriptor;
+    use super::*;
+    use ::fixtures::PAT;
+
+    // TSDT with a DVB transport_stream_descriptor
+    const TSDT: [u8; 17] = [0x03, 0xb0, 0x0e, 0xff, 0xff, 0xc1, 0x00, 0x00, 0x67, 0x03, 0x44, 0x56,
+        0x42, 0x3b, 0x57, 0xb1, 0xa8];
+
+    #[test]
+    fn pat_round_trip() {
+        let pat = ProgramAssociationSection::from_bytes(&PAT).unwrap();
+        assert_eq!(pat.transport_stream_id, 1);
+        assert_eq!(pat.version_number, 0);
+        assert!(pat.current_next_indicator);
+        match pat.associations[..] {
+            [ProgramAssociation::ProgramMapPid { program_number: 1, program_map_pid: 0x100 }] => {},
+            ref associations => panic!("unexpected associations {:?}", associations),
+        }
+        assert_eq!(pat.to_bytes().unwrap(), PAT.to_vec());
+    }
+
+    #[test]
+    fn pat_crc_mismatch() {
+        let mut bytes = PAT.to_vec();
+        bytes[11] = 0x01;
+        match ProgramAssociationSection::from_bytes(&bytes) {
+            Err(DeserializationError::CrcMismatch { expected: 0xe8f95e7d, .. }) => {},
+            result => panic!("unexpected result {:?}", result),
+        }
+        let options = DeserializeOptions { verify_crc: false };
+        let pat = ProgramAssociationSection::from_bytes_with_options(&bytes, &options).unwrap();
+        assert_eq!(pat.associations.len(), 1);
+    }
+
+    #[test]
+    fn tsdt_round_trip() {
+        let tsdt = TransportStreamDescriptionSection::from_bytes(&TSDT).unwrap();
+        assert_eq!(tsdt.version_number, 0);
+        assert!(tsdt.current_next_indicator);
+        let descriptor = find_descriptor::<TransportStreamDescriptor>(&tsdt.descriptors).unwrap();
+        assert_eq!(descriptor.bytes, b"DVB".to_vec());
+        assert_eq!(tsdt.to_bytes().unwrap(), TSDT.to_vec());
+    }
+}
@@ -0,0 +1,749 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # DVB Service Information (SI)
+
+use ::base::*;
+use super::{bits_remaining,short_section_bits_remaining};
+use ::descriptor::{Descriptor,deserialize_descriptor};
+use ::time::{DvbDateTime,DvbDuration};
+use bitreader::BitReader;
+use bitwriter::{BitWriter,WriteInto};
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct TransportStreamInfo {
+        pub transport_stream_id: u16,
+        pub original_network_id: u16,
+        pub descriptors: Vec<Box<Descriptor>>
+    }
+    deserialize(reader) {
+        transport_stream_id: { 16 },
+        original_network_id: { 16 },
+        reserved: { 4 }, // reserved_future_use
+        transport_descriptors_length: { 12, type: u64, length_of: descriptors },
+        descriptors: { value: try!(read_descriptors(transport_descriptors_length, reader)) }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct NetworkInformationSection {
+        pub table_id: u8,
+        pub network_id: u16,
+        pub version_number: u8,
+        pub current_next_indicator: bool,
+        pub section_number: u8,
+        pub last_section_number: u8,
+        pub descriptors: Vec<Box<Descriptor>>,
+        pub transport_streams: Vec<TransportStreamInfo>
+    }
+    deserialize(reader) {
+        // 0x40 for actual network, 0x41 for other network
+        table_id: { value: try!(read_table_id(reader, &[0x40, 0x41])) },
+        expect: { bits: 1, reference: 1 }, // section_syntax_indicator
+        reserved: { 1 }, // reserved_future_use
+        reserved: { 2 },
+        section_length: { 12, type: u16, length_of: rest },
+        network_id: { 16 },
+        reserved: { 2 },
+        version_number: { 5 },
+        current_next_indicator: { 1, map: bool_flag },
+        section_number: { 8 },
+        last_section_number: { 8 },
+        reserved: { 4 }, // reserved_future_use
+        network_descriptors_length: { 12, type: u64, length_of: descriptors },
+        descriptors: { value: try!(read_descriptors(try!(loop_length(network_descriptors_length, section_length, reader)), reader)) },
+        reserved: { 4 }, // reserved_future_use
+        transport_stream_loop_length: { 12, type: u64, length_of: transport_streams },
+        transport_streams: { value: try!(read_repeated(try!(loop_length(transport_stream_loop_length, section_length, reader)) as usize, reader)) },
+        skip: { try!(bits_remaining(section_length, reader)) },
+        crc: { 32 }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct BouquetAssociationSection {
+        pub bouquet_id: u16,
+        pub version_number: u8,
+        pub current_next_indicator: bool,
+        pub section_number: u8,
+        pub last_section_number: u8,
+        pub descriptors: Vec<Box<Descriptor>>,
+        pub transport_streams: Vec<TransportStreamInfo>
+    }
+    deserialize(reader) {
+        expect: { bits: 8, reference: 0x4a }, // table_id
+        expect: { bits: 1, reference: 1 }, // section_syntax_indicator
+        reserved: { 1 }, // reserved_future_use
+        reserved: { 2 },
+        section_length: { 12, type: u16, length_of: rest },
+        bouquet_id: { 16 },
+        reserved: { 2 },
+        version_number: { 5 },
+        current_next_indicator: { 1, map: bool_flag },
+        section_number: { 8 },
+        last_section_number: { 8 },
+        reserved: { 4 }, // reserved_future_use
+        bouquet_descriptors_length: { 12, type: u64, length_of: descriptors },
+        descriptors: { value: try!(read_descriptors(try!(loop_length(bouquet_descriptors_length, section_length, reader)), reader)) },
+        reserved: { 4 }, // reserved_future_use
+        transport_stream_loop_length: { 12, type: u64, length_of: transport_streams },
+        transport_streams: { value: try!(read_repeated(try!(loop_length(transport_stream_loop_length, section_length, reader)) as usize, reader)) },
+        skip: { try!(bits_remaining(section_length, reader)) },
+        crc: { 32 }
+    }
+);
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum RunningStatus {
+    Undefined,
+    NotRunning,
+    StartsInAFewSeconds,
+    Pausing,
+    Running,
+    ServiceOffAir,
+    Reserved(u8),
+}
+
+impl From<u8> for RunningStatus {
+    fn from(value: u8) -> RunningStatus {
+        match value {
+            0 => RunningStatus::Undefined,
+            1 => RunningStatus::NotRunning,
+            2 => RunningStatus::StartsInAFewSeconds,
+            3 => RunningStatus::Pausing,
+            4 => RunningStatus::Running,
+            5 => RunningStatus::ServiceOffAir,
+            other => RunningStatus::Reserved(other),
+        }
+    }
+}
+
+impl WriteInto for RunningStatus {
+    fn write(&self, writer: &mut BitWriter, bits: u8) -> ::bitwriter::Result<()> {
+        let value = match *self {
+            RunningStatus::Undefined => 0,
+            RunningStatus::NotRunning => 1,
+            RunningStatus::StartsInAFewSeconds => 2,
+            RunningStatus::Pausing => 3,
+            RunningStatus::Running => 4,
+            RunningStatus::ServiceOffAir => 5,
+            RunningStatus::Reserved(value) => value,
+        };
+        writer.write_u8(bits, value)
+    }
+}
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct ServiceDescription {
+        pub service_id: u16,
+        pub eit_schedule_flag: bool,
+        pub eit_present_following_flag: bool,
+        pub running_status: RunningStatus,
+        pub free_ca_mode: bool,
+        pub descriptors: Vec<Box<Descriptor>>
+    }
+    deserialize(reader) {
+        service_id: { 16 },
+        reserved: { 6 }, // reserved_future_use
+        eit_schedule_flag: { 1, map: bool_flag },
+        eit_present_following_flag: { 1, map: bool_flag },
+        running_status: { 3, map: |value: u8| RunningStatus::from(value) },
+        free_ca_mode: { 1, map: bool_flag },
+        descriptors_loop_length: { 12, type: u64, length_of: descriptors },
+        descriptors: { value: try!(read_descriptors(descriptors_loop_length, reader)) }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct ServiceDescriptionSection {
+        pub table_id: u8,
+        pub transport_stream_id: u16,
+        pub version_number: u8,
+        pub current_next_indicator: bool,
+        pub section_number: u8,
+        pub last_section_number: u8,
+        pub original_network_id: u16,
+        pub services: Vec<ServiceDescription>
+    }
+    deserialize(reader) {
+        // 0x42 for actual transport stream, 0x46 for other transport stream
+        table_id: { value: try!(read_table_id(reader, &[0x42, 0x46])) },
+        expect: { bits: 1, reference: 1 }, // section_syntax_indicator
+        reserved: { 1 }, // reserved_future_use
+        reserved: { 2 },
+        section_length: { 12, type: u16, length_of: rest },
+        transport_stream_id: { 16 },
+        reserved: { 2 },
+        version_number: { 5 },
+        current_next_indicator: { 1, map: bool_flag },
+        section_number: { 8 },
+        last_section_number: { 8 },
+        original_network_id: { 16 },
+        reserved: { 8 }, // reserved_future_use
+        services: { value: {
+            let mut services = vec![];
+            while try!(bits_remaining(section_length, reader)) > 0 {
+                services.push(try!(Deserialize::deserialize(reader)));
+            }
+            services
+        } },
+        skip: { try!(bits_remaining(section_length, reader)) },
+        crc: { 32 }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct Event {
+        pub event_id: u16,
+        pub start_time: DvbDateTime,
+        pub duration: DvbDuration,
+        pub running_status: RunningStatus,
+        pub free_ca_mode: bool,
+        pub descriptors: Vec<Box<Descriptor>>
+    }
+    deserialize(reader) {
+        event_id: { 16 },
+        start_time: { value: try!(Deserialize::deserialize(reader)) },
+        duration: { value: try!(Deserialize::deserialize(reader)) },
+        running_status: { 3, map: |value: u8| RunningStatus::from(value) },
+        free_ca_mode: { 1, map: bool_flag },
+        descriptors_loop_length: { 12, type: u64, length_of: descriptors },
+        descriptors: { value: try!(read_descriptors(descriptors_loop_length, reader)) }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct EventInformationSection {
+        pub table_id: u8,
+        pub service_id: u16,
+        pub version_number: u8,
+        pub current_next_indicator: bool,
+        pub section_number: u8,
+        pub last_section_number: u8,
+        pub transport_stream_id: u16,
+        pub original_network_id: u16,
+        pub segment_last_section_number: u8,
+        pub last_table_id: u8,
+        pub events: Vec<Event>
+    }
+    deserialize(reader) {
+        // 0x4e and 0x4f for present/following of actual and other transport stream,
+        // 0x50-0x5f and 0x60-0x6f for schedule of actual and other transport stream
+        table_id: { value: try!(read_table_id_range(reader, 0x4e, 0x6f)) },
+        expect: { bits: 1, reference: 1 }, // section_syntax_indicator
+        reserved: { 1 }, // reserved_future_use
+        reserved: { 2 },
+        section_length: { 12, type: u16, length_of: rest },
+        service_id: { 16 },
+        reserved: { 2 },
+        version_number: { 5 },
+        current_next_indicator: { 1, map: bool_flag },
+        section_number: { 8 },
+        last_section_number: { 8 },
+        transport_stream_id: { 16 },
+        original_network_id: { 16 },
+        segment_last_section_number: { 8 },
+        last_table_id: { 8 },
+        events: { value: {
+            let mut events = vec![];
+            while try!(bits_remaining(section_length, reader)) > 0 {
+                events.push(try!(Deserialize::deserialize(reader)));
+            }
+            events
+        } },
+        skip: { try!(bits_remaining(section_length, reader)) },
+        crc: { 32 }
+    }
+);
+
+impl EventInformationSection {
+    pub fn is_present_following(&self) -> bool {
+        self.table_id == 0x4e || self.table_id == 0x4f
+    }
+
+    pub fn is_schedule(&self) -> bool {
+        self.table_id >= 0x50 && self.table_id <= 0x6f
+    }
+}
+
+bit_struct!(
+    #[derive(Debug,Copy,Clone)]
+    pub struct TimeDateSection {
+        pub utc_time: DvbDateTime
+    }
+    deserialize(reader) {
+        expect: { bits: 8, reference: 0x70 }, // table_id
+        expect: { bits: 1, reference: 0 }, // section_syntax_indicator
+        reserved: { 1 }, // reserved_future_use
+        reserved: { 2 },
+        section_length: { 12, type: u16, length_of: rest },
+        utc_time: { value: try!(Deserialize::deserialize(reader)) },
+        skip: { try!(short_section_bits_remaining(section_length, reader)) }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct TimeOffsetSection {
+        pub utc_time: DvbDateTime,
+        pub descriptors: Vec<Box<Descriptor>>
+    }
+    deserialize(reader) {
+        expect: { bits: 8, reference: 0x73 }, // table_id
+        expect: { bits: 1, reference: 0 }, // section_syntax_indicator
+        reserved: { 1 }, // reserved_future_use
+        reserved: { 2 },
+        section_length: { 12, type: u16, length_of: rest },
+        utc_time: { value: try!(Deserialize::deserialize(reader)) },
+        reserved: { 4 },
+        descriptors_loop_length: { 12, type: u64, length_of: descriptors },
+        descriptors: { value: try!(read_descriptors(try!(loop_length(descriptors_loop_length, section_length, reader)), reader)) },
+        skip: { try!(bits_remaining(section_length, reader)) },
+        crc: { 32 }
+    }
+);
+
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct RunningStatusInfo {
+        pub transport_stream_id: u16,
+        pub original_network_id: u16,
+        pub service_id: u16,
+        pub event_id: u16,
+        pub running_status: RunningStatus
+    }
+    deserialize(reader) {
+        transport_stream_id: { 16 },
+        original_network_id: { 16 },
+        service_id: { 16 },
+        event_id: { 16 },
+        reserved: { 5 }, // reserved_future_use
+        running_status: { 3, map: |value: u8| RunningStatus::from(value) }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct RunningStatusSection {
+        pub statuses: Vec<RunningStatusInfo>
+    }
+    deserialize(reader) {
+        expect: { bits: 8, reference: 0x71 }, // table_id
+        expect: { bits: 1, reference: 0 }, // section_syntax_indicator
+        reserved: { 1 }, // reserved_future_use
+        reserved: { 2 },
+        section_length: { 12, type: u16, length_of: rest },
+        statuses: { value: try!(read_repeated(section_length as usize, reader)) }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct StuffingSection {
+        pub section_syntax_indicator: bool,
+        pub data: Vec<u8>
+    }
+    deserialize(reader) {
+        expect: { bits: 8, reference: 0x72 }, // table_id
+        // Stuffing sections may use either syntax, but never have a CRC
+        section_syntax_indicator: { 1, map: bool_flag },
+        reserved: { 1 }, // reserved_future_use
+        reserved: { 2 },
+        section_length: { 12, type: u16, length_of: rest },
+        data: { value: {
+            let mut data = Vec::with_capacity(section_length as usize);
+            for _ in 0..section_length {
+                data.push(try!(reader.read_u8(8)));
+            }
+            data
+        } }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct DiscontinuityInformationSection {
+        pub transition_flag: bool
+    }
+    deserialize(reader) {
+        expect: { bits: 8, reference: 0x7e }, // table_id
+        expect: { bits: 1, reference: 0 }, // section_syntax_indicator
+        reserved: { 1 }, // reserved_future_use
+        reserved: { 2 },
+        section_length: { 12, type: u16, length_of: rest },
+        transition_flag: { 1, map: bool_flag },
+        reserved: { 7 }, // reserved_future_use
+        skip: { try!(short_section_bits_remaining(section_length, reader)) }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct SelectionInformationService {
+        pub service_id: u16,
+        pub running_status: RunningStatus,
+        pub descriptors: Vec<Box<Descriptor>>
+    }
+    deserialize(reader) {
+        service_id: { 16 },
+        reserved: { 1 }, // reserved_future_use
+        running_status: { 3, map: |value: u8| RunningStatus::from(value) },
+        service_loop_length: { 12, type: u64, length_of: descriptors },
+        descriptors: { value: try!(read_descriptors(service_loop_length, reader)) }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug)]
+    pub struct SelectionInformationSection {
+        pub version_number: u8,
+        pub current_next_indicator: bool,
+        pub section_number: u8,
+        pub last_section_number: u8,
+        pub descriptors: Vec<Box<Descriptor>>,
+        pub services: Vec<SelectionInformationService>
+    }
+    deserialize(reader) {
+        expect: { bits: 8, reference: 0x7f }, // table_id
+        expect: { bits: 1, reference: 1 }, // section_syntax_indicator
+        reserved: { 1 }, // reserved_future_use
+        reserved: { 2 },
+        section_length: { 12, type: u16, length_of: rest },
+        reserved: { 16 }, // reserved_future_use
+        reserved: { 2 },
+        version_number: { 5 },
+        current_next_indicator: { 1, map: bool_flag },
+        section_number: { 8 },
+        last_section_number: { 8 },
+        reserved: { 4 }, // reserved_future_use
+        transmission_info_loop_length: { 12, type: u64, length_of: descriptors },
+        descriptors: { value: try!(read_descriptors(try!(loop_length(transmission_info_loop_length, section_length, reader)), reader)) },
+        services: { value: {
+            let mut services = vec![];
+            while try!(bits_remaining(section_length, reader)) > 0 {
+                services.push(try!(Deserialize::deserialize(reader)));
+            }
+            services
+        } },
+        skip: { try!(bits_remaining(section_length, reader)) },
+        crc: { 32 }
+    }
+);
+
+
+fn read_table_id(reader: &mut BitReader, table_ids: &[u8]) -> DeserializationResult<u8> {
+    let position = reader.position();
+    let table_id = try!(reader.read_u8(8));
+    if !table_ids.contains(&table_id) {
+        return Err(DeserializationError::UnexpectedValue {
+            position: position,
+            length: 8,
+            expected: table_ids[0] as u64,
+            got: table_id as u64,
+        });
+    }
+    Ok(table_id)
+}
+
+fn read_table_id_range(reader: &mut BitReader, first: u8, last: u8) -> DeserializationResult<u8> {
+    let position = reader.position();
+    let table_id = try!(reader.read_u8(8));
+    if table_id < first || table_id > last {
+        return Err(DeserializationError::UnexpectedValue {
+            position: position,
+            length: 8,
+            expected: first as u64,
+            got: table_id as u64,
+        });
+    }
+    Ok(table_id)
+}
+
+// Checks that a loop of `length` bytes ends within the section
+fn loop_length(length: u64, section_length: u16, reader: &BitReader) -> DeserializationResult<u64> {
+    let bits_remaining = try!(bits_remaining(section_length, reader));
+    if length * 8 > bits_remaining {
+        return Err(DeserializationError::ReadTooMuch {
+            position: reader.position() + length * 8,
+            max_position: reader.position() + bits_remaining,
+        });
+    }
+    Ok(length)
+}
+
+fn read_descriptors(length: u64, reader: &mut BitReader) -> DeserializationResult<Vec<Box<Descriptor>>> {
+    let mut descriptors = vec![];
+    let mut bits_remaining = length * 8;
+    while bits_remaining > 0 {
+        let start_pos = reader.position();
+        descriptors.push(try!(deserialize_descriptor(reader)));
+        let bits = reader.position() - start_pos;
+        if bits > bits_remaining {
+            return Err(DeserializationError::ReadTooMuch {
+                position: reader.position(),
+                max_position: reader.position() - bits + bits_remaining,
+            });
+        }
+        bits_remaining = bits_remaining - bits;
+    }
+    Ok(descriptors)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::base::*;
+    use ::descriptor::find_descriptor;
+    use ::descriptor::dvb::{NetworkNameDescriptor,ServiceDescriptor,ShortEventDescriptor};
+    use super::*;
+
+    const SDT: [u8; 31] = [0x42, 0xf0, 0x1c, 0x00, 0x01, 0xc3, 0x00, 0x00, 0x00, 0x46, 0xff,
+        0x01, 0x01, 0xfd, 0x80, 0x0b, 0x48, 0x09, 0x01, 0x03, 0x59, 0x4c, 0x45, 0x03, 0x54, 0x56,
+        0x31, 0x1b, 0x51, 0x93, 0x16];
+
+    const EIT: [u8; 44] = [0x4e, 0xf0, 0x29, 0x01, 0x01, 0xc3, 0x00, 0x00, 0x00, 0x01, 0x00,
+        0x46, 0x00, 0x4e, 0x00, 0x07, 0xdf, 0xb2, 0x12, 0x30, 0x00, 0x01, 0x30, 0x00, 0x80, 0x0e,
+        0x4d, 0x0c, 0x66, 0x69, 0x6e, 0x07, 0x55, 0x75, 0x74, 0x69, 0x73, 0x65, 0x74, 0x00, 0x30,
+        0x5c, 0x2e, 0x23];
+
+    const NIT: [u8; 28] = [0x40, 0xf0, 0x19, 0x30, 0x01, 0xc5, 0x00, 0x00, 0xf0, 0x06, 0x40, 0x04,
+        0x54, 0x65, 0x73, 0x74, 0xf0, 0x06, 0x00, 0x01, 0x00, 0x46, 0xf0, 0x00, 0x0d, 0x70, 0x89,
+        0x99];
+
+    const SDT_OTHER: [u8; 15] = [0x46, 0xf0, 0x0c, 0x00, 0x02, 0xc1, 0x00, 0x00, 0x00, 0x46, 0xff,
+        0x48, 0xf0, 0x38, 0xa7];
+
+    const EIT_SCHEDULE: [u8; 30] = [0x50, 0xf0, 0x1b, 0x01, 0x01, 0xc1, 0x00, 0x08, 0x00, 0x01,
+        0x00, 0x46, 0x00, 0x50, 0x00, 0x01, 0xdf, 0xb2, 0x00, 0x00, 0x00, 0x00, 0x30, 0x00, 0x80,
+        0x00, 0x3a, 0x44, 0x11, 0xc5];
+
+    const TDT: [u8; 8] = [0x70, 0x70, 0x05, 0xdf, 0xb2, 0x12, 0x30, 0x00];
+
+    const TOT: [u8; 14] = [0x73, 0x70, 0x0b, 0xdf, 0xb2, 0x12, 0x30, 0x00, 0xf0, 0x00, 0xde, 0xc3,
+        0x75, 0x23];
+
+    const BAT: [u8; 22] = [0x4a, 0xf0, 0x13, 0x00, 0x10, 0xc1, 0x00, 0x00, 0xf0, 0x00, 0xf0, 0x06,
+        0x00, 0x01, 0x00, 0x46, 0xf0, 0x00, 0xa7, 0x84, 0xa2, 0x7c];
+
+    const RST: [u8; 12] = [0x71, 0x70, 0x09, 0x00, 0x01, 0x00, 0x46, 0x01, 0x01, 0x00, 0x07, 0xfc];
+
+    const DIT: [u8; 4] = [0x7e, 0x70, 0x01, 0xff];
+
+    const SIT: [u8; 18] = [0x7f, 0xf0, 0x0f, 0xff, 0xff, 0xc1, 0x00, 0x00, 0xf0, 0x00, 0x01, 0x01,
+        0xc0, 0x00, 0xe5, 0x01, 0xb1, 0xb9];
+
+    #[test]
+    fn sdt_round_trip() {
+        let sdt = ServiceDescriptionSection::from_bytes(&SDT).unwrap();
+        assert_eq!(sdt.table_id, 0x42);
+        assert_eq!(sdt.version_number, 1);
+        assert_eq!(sdt.original_network_id, 0x46);
+        assert_eq!(sdt.services.len(), 1);
+        let service = &sdt.services[0];
+        assert_eq!(service.service_id, 0x101);
+        assert!(!service.eit_schedule_flag);
+        assert!(service.eit_present_following_flag);
+        assert_eq!(service.running_status, RunningStatus::Running);
+        let descriptor = find_descriptor::<ServiceDescriptor>(&service.descriptors).unwrap();
+        assert_eq!(descriptor.service_provider_name, "YLE");
+        assert_eq!(descriptor.service_name, "TV1");
+        assert_eq!(sdt.to_bytes().unwrap(), SDT.to_vec());
+    }
+
+    #[test]
+    fn eit_round_trip() {
+        let eit = EventInformationSection::from_bytes(&EIT).unwrap();
+        assert!(eit.is_present_following());
+        assert_eq!(eit.service_id, 0x101);
+        assert_eq!(eit.events.len(), 1);
+        let event = &eit.events[0];
+        assert_eq!(event.event_id, 7);
+        assert_eq!(event.start_time.date(), Ok((2015, 9, 1)));
+        assert_eq!(event.start_time.seconds_of_day().unwrap(), 12 * 3600 + 30 * 60);
+        assert_eq!(event.duration.to_seconds().unwrap(), 90 * 60);
+        let descriptor = find_descriptor::<ShortEventDescriptor>(&event.descriptors).unwrap();
+        assert_eq!(descriptor.iso_639_language_code, "fin");
+        assert_eq!(descriptor.event_name, "Uutiset");
+        assert_eq!(eit.to_bytes().unwrap(), EIT.to_vec());
+    }
+
+    #[test]
+    fn nit_round_trip() {
+        let nit = NetworkInformationSection::from_bytes(&NIT).unwrap();
+        assert_eq!(nit.table_id, 0x40);
+        assert_eq!(nit.network_id, 0x3001);
+        assert_eq!(nit.version_number, 2);
+        let descriptor = find_descriptor::<NetworkNameDescriptor>(&nit.descriptors).unwrap();
+        assert_eq!(descriptor.name, "Test");
+        assert_eq!(nit.transport_streams.len(), 1);
+        assert_eq!(nit.transport_streams[0].transport_stream_id, 1);
+        assert_eq!(nit.transport_streams[0].original_network_id, 0x46);
+        assert!(nit.transport_streams[0].descriptors.is_empty());
+        assert_eq!(nit.to_bytes().unwrap(), NIT.to_vec());
+
+        let mut other = NIT.to_vec();
+        other[0] = 0x42;
+        assert!(NetworkInformationSection::from_bytes(&other).is_err());
+    }
+
+    #[test]
+    fn sdt_other() {
+        let sdt = ServiceDescriptionSection::from_bytes(&SDT_OTHER).unwrap();
+        assert_eq!(sdt.table_id, 0x46);
+        assert_eq!(sdt.transport_stream_id, 2);
+        assert!(sdt.services.is_empty());
+        assert_eq!(sdt.to_bytes().unwrap(), SDT_OTHER.to_vec());
+    }
+
+    #[test]
+    fn eit_schedule() {
+        let eit = EventInformationSection::from_bytes(&EIT_SCHEDULE).unwrap();
+        assert!(eit.is_schedule());
+        assert!(!eit.is_present_following());
+        assert_eq!(eit.last_section_number, 8);
+        assert_eq!(eit.segment_last_section_number, 0);
+        assert_eq!(eit.last_table_id, 0x50);
+        assert_eq!(eit.events.len(), 1);
+        assert_eq!(eit.events[0].event_id, 1);
+        assert_eq!(eit.events[0].running_status, RunningStatus::Running);
+        assert!(eit.events[0].descriptors.is_empty());
+        assert_eq!(eit.to_bytes().unwrap(), EIT_SCHEDULE.to_vec());
+    }
+
+    #[test]
+    fn tdt_round_trip() {
+        let tdt = TimeDateSection::from_bytes(&TDT).unwrap();
+        assert_eq!(tdt.utc_time.mjd, 0xdfb2);
+        assert_eq!(tdt.utc_time.utc, 0x123000);
+        assert_eq!(tdt.to_bytes().unwrap(), TDT.to_vec());
+    }
+
+    #[test]
+    fn tot_round_trip() {
+        let tot = TimeOffsetSection::from_bytes(&TOT).unwrap();
+        assert_eq!(tot.utc_time, TimeDateSection::from_bytes(&TDT).unwrap().utc_time);
+        assert!(tot.descriptors.is_empty());
+        assert_eq!(tot.to_bytes().unwrap(), TOT.to_vec());
+
+        let mut bytes = TOT.to_vec();
+        bytes[13] ^= 0x01;
+        assert!(TimeOffsetSection::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn bat_round_trip() {
+        let bat = BouquetAssociationSection::from_bytes(&BAT).unwrap();
+        assert_eq!(bat.bouquet_id, 0x10);
+        assert!(bat.descriptors.is_empty());
+        assert_eq!(bat.transport_streams.len(), 1);
+        assert_eq!(bat.transport_streams[0].transport_stream_id, 1);
+        assert_eq!(bat.transport_streams[0].original_network_id, 0x46);
+        assert_eq!(bat.to_bytes().unwrap(), BAT.to_vec());
+    }
+
+    #[test]
+    fn rst_round_trip() {
+        let rst = RunningStatusSection::from_bytes(&RST).unwrap();
+        assert_eq!(rst.statuses.len(), 1);
+        let status = &rst.statuses[0];
+        assert_eq!(status.transport_stream_id, 1);
+        assert_eq!(status.original_network_id, 0x46);
+        assert_eq!(status.service_id, 0x101);
+        assert_eq!(status.event_id, 7);
+        assert_eq!(status.running_status, RunningStatus::Running);
+        assert_eq!(rst.to_bytes().unwrap(), RST.to_vec());
+    }
+
+    #[test]
+    fn st_round_trip() {
+        let bytes = [0x72, 0x70, 0x03, 0xff, 0xff, 0xff];
+        let st = StuffingSection::from_bytes(&bytes).unwrap();
+        assert!(!st.section_syntax_indicator);
+        assert_eq!(st.data, vec![0xff; 3]);
+        assert_eq!(st.to_bytes().unwrap(), bytes.to_vec());
+    }
+
+    #[test]
+    fn dit_round_trip() {
+        let dit = DiscontinuityInformationSection::from_bytes(&DIT).unwrap();
+        assert!(dit.transition_flag);
+        assert_eq!(dit.to_bytes().unwrap(), DIT.to_vec());
+    }
+
+    #[test]
+    fn sit_round_trip() {
+        let sit = SelectionInformationSection::from_bytes(&SIT).unwrap();
+        assert!(sit.descriptors.is_empty());
+        assert_eq!(sit.services.len(), 1);
+        assert_eq!(sit.services[0].service_id, 0x101);
+        assert_eq!(sit.services[0].running_status, RunningStatus::Running);
+        assert_eq!(sit.to_bytes().unwrap(), SIT.to_vec());
+    }
+
+    #[test]
+    fn time_section_length_too_short() {
+        let mut tdt = TDT;
+        tdt[2] = 0x00;
+        let mut tot = TOT;
+        tot[2] = 0x02;
+        match TimeDateSection::from_bytes(&tdt) {
+            Err(DeserializationError::ReadTooMuch { .. }) => {},
+            result => panic!("unexpected result {:?}", result),
+        }
+        match TimeOffsetSection::from_bytes(&tot) {
+            Err(DeserializationError::ReadTooMuch { .. }) => {},
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn loop_length_too_long() {
+        // Descriptor and transport stream loops that would continue past the section
+        let mut nit = NIT;
+        nit[17] = 0x0a;
+        let mut bat = BAT;
+        bat[9] = 0x20;
+        let mut tot = TOT;
+        tot[9] = 0x04;
+        let mut sit = SIT;
+        sit[9] = 0x10;
+        let results = vec![
+            NetworkInformationSection::from_bytes(&nit).map(|_| ()),
+            BouquetAssociationSection::from_bytes(&bat).map(|_| ()),
+            TimeOffsetSection::from_bytes(&tot).map(|_| ()),
+            SelectionInformationSection::from_bytes(&sit).map(|_| ()),
+        ];
+        for result in results {
+            match result {
+                Err(DeserializationError::ReadTooMuch { .. }) => {},
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+
+        // Descriptor loops of services and events that run into the CRC_32
+        let mut sdt = SDT;
+        sdt[15] = 0x0f;
+        assert!(ServiceDescriptionSection::from_bytes(&sdt).is_err());
+        let mut eit = EIT;
+        eit[25] = 0x12;
+        assert!(EventInformationSection::from_bytes(&eit).is_err());
+    }
+}
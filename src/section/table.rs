@@ -0,0 +1,345 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Collecting sections into complete tables
+
+use std::collections::HashMap;
+
+use ::base::*;
+use bitreader::BitReader;
+use super::{PrivateSectionHeader,bits_remaining};
+
+/// Identifies a table: sections with the same key belong to the same table.
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub struct TableKey {
+    pub table_id: u8,
+    pub table_id_extension: u16,
+    /// Present for EIT
+    pub transport_stream_id: Option<u16>,
+    /// Present for SDT and EIT
+    pub original_network_id: Option<u16>,
+}
+
+/// All sections of a single version of a table, in section_number order.
+#[derive(Debug,Clone)]
+pub struct Table {
+    pub key: TableKey,
+    pub version_number: u8,
+    pub current_next_indicator: bool,
+    pub sections: Vec<Vec<u8>>,
+}
+
+#[derive(Debug,Clone)]
+pub enum TableEvent {
+    /// The first complete version of a table
+    Completed(Table),
+    /// A table was completed with a version number different from the previously completed one
+    VersionChanged {
+        previous_version: u8,
+        table: Table,
+    },
+}
+
+#[derive(Debug,Clone)]
+struct TableState {
+    version_number: u8,
+    last_section_number: u8,
+    sections: Vec<Option<Vec<u8>>>,
+    // EIT sections after segment_last_section_number are never sent
+    expected: Vec<bool>,
+    completed_version: Option<u8>,
+}
+
+impl TableState {
+    fn new(version_number: u8, last_section_number: u8, completed_version: Option<u8>) -> TableState {
+        let count = last_section_number as usize + 1;
+        TableState {
+            version_number: version_number,
+            last_section_number: last_section_number,
+            sections: vec![None; count],
+            expected: vec![true; count],
+            completed_version: completed_version,
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.sections.iter().zip(self.expected.iter()).all(|(section, expected)| section.is_some() || !expected)
+    }
+}
+
+/// Collects long-form sections into tables, keeping track of table versions. Short-form sections
+/// (such as TDT and TOT) are complete on their own and are not collected.
+#[derive(Debug,Clone)]
+pub struct TableCollector {
+    tables: HashMap<(TableKey, bool), TableState>,
+    include_next: bool,
+    options: DeserializeOptions,
+}
+
+impl TableCollector {
+    pub fn new() -> TableCollector {
+        TableCollector {
+            tables: HashMap::new(),
+            include_next: false,
+            options: DeserializeOptions::new(),
+        }
+    }
+
+    /// Sections with a CRC_32 that does not match are rejected unless `verify_crc` is disabled in
+    /// the options.
+    pub fn set_options(&mut self, options: DeserializeOptions) {
+        self.options = options;
+    }
+
+    /// By default sections with current_next_indicator set to false (tables that are not yet
+    /// applicable) are ignored. When included, they are collected separately from the current
+    /// tables.
+    pub fn include_next_sections(&mut self, include: bool) {
+        self.include_next = include;
+    }
+
+    /// Forget the sections and the completed versions of a table, so that its next complete
+    /// version is reported as `Completed`.
+    pub fn forget(&mut self, key: &TableKey) {
+        self.tables.remove(&(*key, true));
+        self.tables.remove(&(*key, false));
+    }
+
+    /// Feed a complete section to the collector. Returns an event when the section completes
+    /// a table version that has not been completed before.
+    pub fn push(&mut self, section: &[u8]) -> DeserializationResult<Option<TableEvent>> {
+        let mut reader = BitReader::new(section);
+        let header: PrivateSectionHeader = try!(Deserialize::deserialize(&mut reader));
+        let extended_header = match header.extended_header {
+            Some(extended_header) => extended_header,
+            None => return Ok(None),
+        };
+        if !extended_header.current_next_indicator && !self.include_next {
+            return Ok(None);
+        }
+        let mut key = TableKey {
+            table_id: header.table_id,
+            table_id_extension: extended_header.table_id_extension,
+            transport_stream_id: None,
+            original_network_id: None,
+        };
+        let mut segment_last_section_number = None;
+        match header.table_id {
+            0x42 | 0x46 => {
+                key.original_network_id = Some(try!(reader.read_u16(16)));
+            },
+            0x4e...0x6f => {
+                key.transport_stream_id = Some(try!(reader.read_u16(16)));
+                key.original_network_id = Some(try!(reader.read_u16(16)));
+                segment_last_section_number = Some(try!(reader.read_u8(8)));
+            },
+            _ => {},
+        }
+        // The CRC_32 is the last field of the section
+        let crc_position = reader.position() + try!(bits_remaining(header.section_length, &reader));
+        let mut crc_reader = BitReader::new(section);
+        try!(crc_reader.skip(crc_position));
+        try!(verify_crc(BitReader::new(section), &mut crc_reader, &self.options));
+
+        let version_number = extended_header.version_number;
+        let section_number = extended_header.section_number;
+        let last_section_number = extended_header.last_section_number;
+        if section_number > last_section_number {
+            return Ok(None);
+        }
+        // Each EIT segment has eight sections, of which the ones after
+        // segment_last_section_number are left out
+        let segment_end = (section_number / 8 * 8).saturating_add(7).min(last_section_number);
+        if let Some(segment_last_section_number) = segment_last_section_number {
+            if segment_last_section_number < section_number || segment_last_section_number > segment_end {
+                return Ok(None);
+            }
+        }
+
+        let state = self.tables.entry((key, extended_header.current_next_indicator))
+            .or_insert_with(|| TableState::new(version_number, last_section_number, None));
+        if state.version_number != version_number || state.last_section_number != last_section_number {
+            *state = TableState::new(version_number, last_section_number, state.completed_version);
+        }
+        state.sections[section_number as usize] = Some(section.to_vec());
+        if let Some(segment_last_section_number) = segment_last_section_number {
+            for expected in (segment_last_section_number as usize + 1)..(segment_end as usize + 1) {
+                state.expected[expected] = false;
+            }
+        }
+
+        if !state.is_complete() || state.completed_version == Some(version_number) {
+            return Ok(None);
+        }
+        let table = Table {
+            key: key,
+            version_number: version_number,
+            current_next_indicator: extended_header.current_next_indicator,
+            sections: state.sections.iter().filter_map(|section| section.clone()).collect(),
+        };
+        let event = match state.completed_version {
+            Some(previous_version) => TableEvent::VersionChanged {
+                previous_version: previous_version,
+                table: table,
+            },
+            None => TableEvent::Completed(table),
+        };
+        state.completed_version = Some(version_number);
+        Ok(Some(event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::fixtures::{section,append_crc};
+
+    // The same section with current_next_indicator cleared
+    fn next_section(mut bytes: Vec<u8>) -> Vec<u8> {
+        let length = bytes.len();
+        bytes[5] &= !0x01;
+        bytes.truncate(length - 4);
+        append_crc(&mut bytes);
+        bytes
+    }
+
+    // EIT schedule section of service 1 with segment_last_section_number
+    fn eit(section_number: u8, last_section_number: u8, segment_last_section_number: u8) -> Vec<u8> {
+        section(0x50, 1, 0, section_number, last_section_number,
+                &[0x00, 0x01, 0x00, 0x46, segment_last_section_number, 0x50])
+    }
+
+    #[test]
+    fn crc_mismatch() {
+        let mut bytes = section(0x00, 1, 0, 0, 0, &[0x00, 0x01, 0xe1, 0x00]);
+        let length = bytes.len();
+        bytes[length - 1] ^= 0x01;
+        let mut collector = TableCollector::new();
+        match collector.push(&bytes) {
+            Err(DeserializationError::CrcMismatch { .. }) => {},
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        collector.set_options(DeserializeOptions { verify_crc: false });
+        match collector.push(&bytes) {
+            Ok(Some(TableEvent::Completed(ref table))) => assert_eq!(table.sections, vec![bytes.clone()]),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn multi_section_table() {
+        let section0 = section(0x42, 1, 3, 0, 1, &[0x00, 0x46, 0xff]);
+        let section1 = section(0x42, 1, 3, 1, 1, &[0x00, 0x46, 0xff]);
+        let mut collector = TableCollector::new();
+        assert!(collector.push(&section1).unwrap().is_none());
+        match collector.push(&section0).unwrap() {
+            Some(TableEvent::Completed(table)) => {
+                assert_eq!(table.key, TableKey {
+                    table_id: 0x42,
+                    table_id_extension: 1,
+                    transport_stream_id: None,
+                    original_network_id: Some(0x46),
+                });
+                assert_eq!(table.version_number, 3);
+                assert_eq!(table.sections, vec![section0.clone(), section1.clone()]);
+            },
+            event => panic!("unexpected event {:?}", event),
+        }
+        // Repetitions of a completed version
+        assert!(collector.push(&section0).unwrap().is_none());
+        assert!(collector.push(&section1).unwrap().is_none());
+    }
+
+    #[test]
+    fn version_change() {
+        let mut collector = TableCollector::new();
+        assert!(collector.push(&section(0x00, 1, 0, 0, 0, &[0x00, 0x01, 0xe1, 0x00])).unwrap().is_some());
+        let new_version = section(0x00, 1, 1, 0, 0, &[0x00, 0x02, 0xe2, 0x00]);
+        let key = match collector.push(&new_version).unwrap() {
+            Some(TableEvent::VersionChanged { previous_version: 0, table }) => {
+                assert_eq!(table.version_number, 1);
+                assert_eq!(table.sections, vec![new_version.clone()]);
+                table.key
+            },
+            event => panic!("unexpected event {:?}", event),
+        };
+        assert!(collector.push(&new_version).unwrap().is_none());
+
+        // A forgotten table is completed again
+        collector.forget(&key);
+        match collector.push(&new_version).unwrap() {
+            Some(TableEvent::Completed(ref table)) => assert_eq!(table.version_number, 1),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn next_sections() {
+        let bytes = next_section(section(0x00, 1, 1, 0, 0, &[0x00, 0x01, 0xe1, 0x00]));
+        let mut collector = TableCollector::new();
+        assert!(collector.push(&bytes).unwrap().is_none());
+        collector.include_next_sections(true);
+        match collector.push(&bytes).unwrap() {
+            Some(TableEvent::Completed(ref table)) => assert!(!table.current_next_indicator),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn eit_segments() {
+        // Two segments with only their first sections present
+        let mut collector = TableCollector::new();
+        assert!(collector.push(&eit(8, 8, 8)).unwrap().is_none());
+        match collector.push(&eit(0, 8, 0)).unwrap() {
+            Some(TableEvent::Completed(table)) => {
+                assert_eq!(table.key.transport_stream_id, Some(1));
+                assert_eq!(table.sections, vec![eit(0, 8, 0), eit(8, 8, 8)]);
+            },
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn invalid_segment_last_section_number() {
+        let mut collector = TableCollector::new();
+        // Beyond the segment of the section, and beyond last_section_number
+        assert!(collector.push(&eit(0, 8, 8)).unwrap().is_none());
+        assert!(collector.push(&eit(8, 9, 10)).unwrap().is_none());
+        // Before the section itself
+        assert!(collector.push(&eit(9, 9, 8)).unwrap().is_none());
+        // None of the invalid sections were kept
+        assert!(collector.push(&eit(0, 9, 0)).unwrap().is_none());
+        assert!(collector.push(&eit(8, 9, 9)).unwrap().is_none());
+        match collector.push(&eit(9, 9, 9)).unwrap() {
+            Some(TableEvent::Completed(table)) => assert_eq!(table.sections.len(), 3),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn section_length_too_short() {
+        let mut collector = TableCollector::new();
+        collector.set_options(DeserializeOptions { verify_crc: false });
+        // No room for the CRC_32, or for the original_network_id of an SDT
+        for &(table_id, section_length) in &[(0x00, 0x05), (0x42, 0x08)] {
+            let mut bytes = section(table_id, 1, 0, 0, 0, &[0x00, 0x01, 0xff]);
+            bytes[2] = section_length;
+            match collector.push(&bytes) {
+                Err(DeserializationError::ReadTooMuch { .. }) => {},
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+    }
+}
@@ -0,0 +1,284 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # DVB date and time fields
+//!
+//! Dates are coded as Modified Julian Date (MJD) and times as binary-coded decimal (BCD), as
+//! described in ETSI EN 300 468 Annex C.
+
+use std::fmt;
+use std::error::Error;
+use std::time::{Duration,SystemTime,UNIX_EPOCH};
+
+use ::base::{decode_bcd,encode_bcd};
+
+// Modified Julian Date of 1970-01-01
+const UNIX_EPOCH_MJD: i64 = 40587;
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+pub type TimeResult<T> = Result<T, TimeError>;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum TimeError {
+    /// Some of the BCD digits were not decimal
+    InvalidBcd(u32),
+    /// All bits of the field were set, which marks the value as undefined
+    Undefined,
+    /// The value is out of the range of the field, or can not be represented in the target type
+    OutOfRange,
+}
+
+impl Error for TimeError {
+    fn description(&self) -> &str {
+        match *self {
+            TimeError::InvalidBcd(..) => "Invalid binary-coded decimal time",
+            TimeError::Undefined => "Time is undefined",
+            TimeError::OutOfRange => "Time is out of the representable range",
+        }
+    }
+}
+
+impl fmt::Display for TimeError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TimeError::InvalidBcd(value) => write!(fmt, "Invalid binary-coded decimal time {:06x}", value),
+            TimeError::Undefined => write!(fmt, "Time is undefined"),
+            TimeError::OutOfRange => write!(fmt, "Time is out of the representable range"),
+        }
+    }
+}
+
+bit_struct!(
+    #[derive(Debug,Clone,Copy,PartialEq,Eq)]
+    pub struct DvbDateTime {
+        pub mjd: u16,
+        pub utc: u32
+    }
+    deserialize(reader) {
+        mjd: { 16 },
+        utc: { 24 }
+    }
+);
+
+impl DvbDateTime {
+    pub fn is_undefined(&self) -> bool {
+        self.mjd == 0xffff && self.utc == 0xffffff
+    }
+
+    /// The calendar date as (year, month, day), using the conversion formula of EN 300 468 Annex C.
+    /// The formula is valid from 1900-03-01 to 2100-02-28.
+    pub fn date(&self) -> TimeResult<(u32, u8, u8)> {
+        if self.mjd == 0xffff {
+            return Err(TimeError::Undefined);
+        }
+        let mjd = self.mjd as f64;
+        let y = ((mjd - 15078.2) / 365.25).floor();
+        let m = ((mjd - 14956.1 - (y * 365.25).floor()) / 30.6001).floor();
+        let day = mjd - 14956.0 - (y * 365.25).floor() - (m * 30.6001).floor();
+        let k = if m == 14.0 || m == 15.0 { 1.0 } else { 0.0 };
+        let year = 1900.0 + y + k;
+        let month = m - 1.0 - k * 12.0;
+        Ok((year as u32, month as u8, day as u8))
+    }
+
+    /// The time of day in seconds.
+    pub fn seconds_of_day(&self) -> TimeResult<u32> {
+        if self.is_undefined() {
+            return Err(TimeError::Undefined);
+        }
+        let seconds = try!(hhmmss_to_seconds(self.utc));
+        if seconds >= SECONDS_PER_DAY as u32 {
+            return Err(TimeError::OutOfRange);
+        }
+        Ok(seconds)
+    }
+
+    pub fn to_unix_seconds(&self) -> TimeResult<i64> {
+        let seconds_of_day = try!(self.seconds_of_day()) as i64;
+        Ok((self.mjd as i64 - UNIX_EPOCH_MJD) * SECONDS_PER_DAY + seconds_of_day)
+    }
+
+    pub fn from_unix_seconds(seconds: i64) -> TimeResult<DvbDateTime> {
+        let mjd = UNIX_EPOCH_MJD + div_floor(seconds, SECONDS_PER_DAY);
+        let seconds_of_day = seconds - div_floor(seconds, SECONDS_PER_DAY) * SECONDS_PER_DAY;
+        if mjd < 0 || mjd > 0xffff {
+            return Err(TimeError::OutOfRange);
+        }
+        Ok(DvbDateTime {
+            mjd: mjd as u16,
+            utc: try!(seconds_to_hhmmss(seconds_of_day as u32)),
+        })
+    }
+
+    pub fn to_system_time(&self) -> TimeResult<SystemTime> {
+        let seconds = try!(self.to_unix_seconds());
+        if seconds >= 0 {
+            Ok(UNIX_EPOCH + Duration::from_secs(seconds as u64))
+        } else {
+            Ok(UNIX_EPOCH - Duration::from_secs(-seconds as u64))
+        }
+    }
+
+    /// Converts a system time to DVB time, truncating it to whole seconds.
+    pub fn from_system_time(time: SystemTime) -> TimeResult<DvbDateTime> {
+        let seconds = match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            Err(err) => {
+                let before = err.duration();
+                let rounding = if before.subsec_nanos() > 0 { 1 } else { 0 };
+                -(before.as_secs() as i64) - rounding
+            }
+        };
+        DvbDateTime::from_unix_seconds(seconds)
+    }
+}
+
+bit_struct!(
+    #[derive(Debug,Clone,Copy,PartialEq,Eq)]
+    pub struct DvbDuration {
+        pub bcd: u32
+    }
+    deserialize(reader) {
+        bcd: { 24 }
+    }
+);
+
+impl DvbDuration {
+    /// Duration from 16-bit BCD hours and minutes, as used by the local time offsets.
+    pub fn from_hhmm_bcd(hhmm: u16) -> DvbDuration {
+        DvbDuration {
+            bcd: (hhmm as u32) << 8,
+        }
+    }
+
+    /// The hours and minutes as 16-bit BCD, leaving out the seconds.
+    pub fn hhmm_bcd(&self) -> u16 {
+        (self.bcd >> 8) as u16
+    }
+
+    pub fn is_undefined(&self) -> bool {
+        self.bcd == 0xffffff
+    }
+
+    pub fn to_seconds(&self) -> TimeResult<u32> {
+        if self.is_undefined() {
+            return Err(TimeError::Undefined);
+        }
+        hhmmss_to_seconds(self.bcd)
+    }
+
+    pub fn from_seconds(seconds: u32) -> TimeResult<DvbDuration> {
+        Ok(DvbDuration {
+            bcd: try!(seconds_to_hhmmss(seconds)),
+        })
+    }
+
+    pub fn to_duration(&self) -> TimeResult<Duration> {
+        Ok(Duration::from_secs(try!(self.to_seconds()) as u64))
+    }
+
+    /// Converts a duration to DVB duration, truncating it to whole seconds.
+    pub fn from_duration(duration: Duration) -> TimeResult<DvbDuration> {
+        if duration.as_secs() > 0xffffffff {
+            return Err(TimeError::OutOfRange);
+        }
+        DvbDuration::from_seconds(duration.as_secs() as u32)
+    }
+}
+
+fn hhmmss_to_seconds(bcd: u32) -> TimeResult<u32> {
+    let value = try!(decode_bcd(bcd as u64, 6).ok_or(TimeError::InvalidBcd(bcd))) as u32;
+    let hours = value / 10000;
+    let minutes = value / 100 % 100;
+    let seconds = value % 100;
+    if minutes > 59 || seconds > 59 {
+        return Err(TimeError::OutOfRange);
+    }
+    Ok(hours * 3600 + minutes * 60 + seconds)
+}
+
+fn seconds_to_hhmmss(seconds: u32) -> TimeResult<u32> {
+    let hours = seconds / 3600;
+    let minutes = seconds / 60 % 60;
+    let value = hours as u64 * 10000 + minutes as u64 * 100 + (seconds % 60) as u64;
+    encode_bcd(value, 6).map(|bcd| bcd as u32).ok_or(TimeError::OutOfRange)
+}
+
+fn div_floor(value: i64, divisor: i64) -> i64 {
+    let quotient = value / divisor;
+    if value % divisor < 0 { quotient - 1 } else { quotient }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration,UNIX_EPOCH};
+    use ::base::{decode_bcd,encode_bcd};
+    use super::*;
+
+    #[test]
+    fn annex_c_example() {
+        // 93/10/13 12:45:00 from ETSI EN 300 468 Annex C
+        let time = DvbDateTime { mjd: 0xc079, utc: 0x124500 };
+        assert_eq!(time.date(), Ok((1993, 10, 13)));
+        assert_eq!(time.seconds_of_day(), Ok(12 * 3600 + 45 * 60));
+    }
+
+    #[test]
+    fn unix_time() {
+        let time = DvbDateTime { mjd: 0xdfb2, utc: 0x123000 };
+        assert_eq!(time.date(), Ok((2015, 9, 1)));
+        assert_eq!(time.to_unix_seconds(), Ok(1441110600));
+        assert_eq!(DvbDateTime::from_unix_seconds(1441110600), Ok(time));
+        assert_eq!(time.to_system_time(), Ok(UNIX_EPOCH + Duration::from_secs(1441110600)));
+
+        let before_epoch = DvbDateTime::from_unix_seconds(-1).unwrap();
+        assert_eq!(before_epoch, DvbDateTime { mjd: 40586, utc: 0x235959 });
+        assert_eq!(before_epoch.date(), Ok((1969, 12, 31)));
+        assert_eq!(DvbDateTime::from_system_time(UNIX_EPOCH - Duration::from_millis(500)), Ok(before_epoch));
+    }
+
+    #[test]
+    fn invalid_time() {
+        assert_eq!(DvbDateTime { mjd: 0xdfb2, utc: 0x126000 }.seconds_of_day(), Err(TimeError::OutOfRange));
+        assert_eq!(DvbDateTime { mjd: 0xdfb2, utc: 0x1a0000 }.seconds_of_day(), Err(TimeError::InvalidBcd(0x1a0000)));
+        assert_eq!(DvbDateTime { mjd: 0xdfb2, utc: 0x240000 }.seconds_of_day(), Err(TimeError::OutOfRange));
+        assert_eq!(DvbDateTime { mjd: 0xffff, utc: 0xffffff }.seconds_of_day(), Err(TimeError::Undefined));
+        assert_eq!(DvbDateTime { mjd: 0xffff, utc: 0xffffff }.date(), Err(TimeError::Undefined));
+        assert_eq!(DvbDateTime::from_unix_seconds(-40588 * 86400), Err(TimeError::OutOfRange));
+    }
+
+    #[test]
+    fn duration() {
+        let duration = DvbDuration { bcd: 0x013000 };
+        assert_eq!(duration.to_seconds(), Ok(5400));
+        assert_eq!(duration.to_duration(), Ok(Duration::from_secs(5400)));
+        assert_eq!(DvbDuration::from_seconds(5400), Ok(duration));
+        assert_eq!(duration.hhmm_bcd(), 0x0130);
+        assert_eq!(DvbDuration::from_hhmm_bcd(0x0130), duration);
+        // Six BCD digits fit durations of up to 99 hours
+        assert_eq!(DvbDuration::from_seconds(99 * 3600), Ok(DvbDuration { bcd: 0x990000 }));
+        assert_eq!(DvbDuration::from_seconds(100 * 3600), Err(TimeError::OutOfRange));
+        assert_eq!(DvbDuration { bcd: 0xffffff }.to_seconds(), Err(TimeError::Undefined));
+        assert_eq!(DvbDuration { bcd: 0x016000 }.to_seconds(), Err(TimeError::OutOfRange));
+        assert_eq!(DvbDuration { bcd: 0x0100a0 }.to_seconds(), Err(TimeError::InvalidBcd(0x0100a0)));
+    }
+
+    #[test]
+    fn bcd() {
+        assert_eq!(decode_bcd(0x123456, 6), Some(123456));
+        assert_eq!(decode_bcd(0x12345a, 6), None);
+        assert_eq!(encode_bcd(123456, 6), Some(0x123456));
+        assert_eq!(encode_bcd(1234567, 6), None);
+    }
+}
@@ -0,0 +1,488 @@
+// Copyright 2015 Ilkka Rauta
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Transport Stream (TS) packets
+
+use ::base::*;
+use bitreader::BitReader;
+
+pub const PACKET_SIZE: usize = 188;
+pub const SYNC_BYTE: u8 = 0x47;
+pub const NULL_PID: u16 = 0x1fff;
+
+bit_struct!(
+    #[derive(Debug,Clone)]
+    pub struct TsPacket {
+        pub transport_error_indicator: bool,
+        pub payload_unit_start_indicator: bool,
+        pub transport_priority: bool,
+        pub pid: u16,
+        pub transport_scrambling_control: u8,
+        pub adaptation_field_control: u8,
+        pub continuity_counter: u8,
+        pub adaptation_field: Option<AdaptationField>,
+        pub payload: Vec<u8>
+    }
+    deserialize(reader) {
+        expect: { bits: 8, reference: SYNC_BYTE as u64 }, // sync_byte
+        transport_error_indicator: { 1, map: bool_flag },
+        payload_unit_start_indicator: { 1, map: bool_flag },
+        transport_priority: { 1, map: bool_flag },
+        pid: { 13 },
+        transport_scrambling_control: { 2 },
+        adaptation_field_control: { 2, write: try!(checked_adaptation_field_control(*adaptation_field_control, adaptation_field, payload)) },
+        continuity_counter: { 4 },
+        adaptation_field: { value: if adaptation_field_control & 0b10 != 0 {
+            Some(try!(Deserialize::deserialize(reader)))
+        } else { None }, write: try!(stuffed_adaptation_field(adaptation_field, payload.len())) },
+        payload: { value: if adaptation_field_control & 0b01 != 0 {
+            try!(read_payload(reader))
+        } else { vec![] } },
+        skip: { try!(bits_remaining(reader)) }
+    }
+);
+
+impl TsPacket {
+    pub fn has_adaptation_field(&self) -> bool {
+        self.adaptation_field_control & 0b10 != 0
+    }
+
+    pub fn has_payload(&self) -> bool {
+        self.adaptation_field_control & 0b01 != 0
+    }
+}
+
+/// How the payload of a packet relates to the previous packets of its PID.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Continuity {
+    /// The payload follows the payload of the previous packet
+    Continuous,
+    /// Packets have been lost before this one, so data collected so far is incomplete
+    Discontinuous,
+    /// The packet has no new payload: it only has an adaptation field, or it is a duplicate
+    NoPayload,
+    /// The packet has transport_error_indicator set and can not be used
+    TransportError,
+}
+
+/// Follows the continuity_counter of the packets of a single PID.
+#[derive(Debug,Clone,Copy)]
+pub struct ContinuityTracker {
+    continuity_counter: Option<u8>,
+    // The previous packet was a duplicate, and another one is not allowed
+    duplicate: bool,
+}
+
+impl ContinuityTracker {
+    pub fn new() -> ContinuityTracker {
+        ContinuityTracker {
+            continuity_counter: None,
+            duplicate: false,
+        }
+    }
+
+    /// Forget the previous packet, so that the next one is continuous with anything.
+    pub fn reset(&mut self) {
+        self.continuity_counter = None;
+        self.duplicate = false;
+    }
+
+    pub fn push(&mut self, packet: &TsPacket) -> Continuity {
+        if packet.transport_error_indicator {
+            self.reset();
+            return Continuity::TransportError;
+        }
+        // A signalled discontinuity allows any continuity_counter
+        if packet.adaptation_field.as_ref().map_or(false, |field| field.discontinuity_indicator) {
+            self.reset();
+        }
+        // The continuity counter is not incremented for packets without payload
+        if !packet.has_payload() {
+            return Continuity::NoPayload;
+        }
+        let previous = self.continuity_counter;
+        self.continuity_counter = Some(packet.continuity_counter);
+        let duplicate = self.duplicate;
+        self.duplicate = false;
+        match previous {
+            // A packet may be sent twice, but only once in a row
+            Some(previous) if packet.continuity_counter == previous && !duplicate => {
+                self.duplicate = true;
+                Continuity::NoPayload
+            },
+            Some(previous) if packet.continuity_counter != (previous + 1) & 0xf => Continuity::Discontinuous,
+            _ => Continuity::Continuous,
+        }
+    }
+}
+
+bit_struct!(
+    #[derive(Debug,Clone,Copy)]
+    pub struct ClockReference {
+        pub base: u64,
+        pub extension: u16
+    }
+    deserialize(reader) {
+        base: { 33 },
+        reserved: { 6 },
+        extension: { 9 }
+    }
+);
+
+impl ClockReference {
+    /// Clock reference value in 27 MHz ticks.
+    pub fn ticks(&self) -> u64 {
+        self.base * 300 + self.extension as u64
+    }
+}
+
+bit_struct!(
+    #[derive(Debug,Clone)]
+    pub struct AdaptationField {
+        pub discontinuity_indicator: bool,
+        pub random_access_indicator: bool,
+        pub elementary_stream_priority_indicator: bool,
+        pub pcr: Option<ClockReference>,
+        pub opcr: Option<ClockReference>,
+        pub splice_countdown: Option<i8>,
+        pub transport_private_data: Option<Vec<u8>>,
+        pub extension: Option<AdaptationFieldExtension>
+    }
+    deserialize(reader) {
+        adaptation_field_length: { 8, type: u8, length_of: rest },
+        // A zero length adaptation field is used for stuffing a single byte and has no flags
+        flags: {
+            value: if adaptation_field_length > 0 { try!(reader.read_u8(8)) } else { 0 },
+            write: flag_bits(&[*discontinuity_indicator, *random_access_indicator,
+                *elementary_stream_priority_indicator, pcr.is_some(), opcr.is_some(),
+                splice_countdown.is_some(), transport_private_data.is_some(), extension.is_some()])
+        },
+        discontinuity_indicator: { value: flags & 0x80 != 0, write: () },
+        random_access_indicator: { value: flags & 0x40 != 0, write: () },
+        elementary_stream_priority_indicator: { value: flags & 0x20 != 0, write: () },
+        pcr: { value: if flags & 0x10 != 0 {
+            Some(try!(Deserialize::deserialize(reader)))
+        } else { None } },
+        opcr: { value: if flags & 0x08 != 0 {
+            Some(try!(Deserialize::deserialize(reader)))
+        } else { None } },
+        splice_countdown: { value: if flags & 0x04 != 0 {
+            Some(try!(reader.read_u8(8)) as i8)
+        } else { None }, write: splice_countdown.map(|countdown| countdown as u8) },
+        transport_private_data: { value: if flags & 0x02 != 0 {
+            let transport_private_data_length = try!(reader.read_u8(8));
+            let mut data = vec![];
+            for _ in 0..transport_private_data_length {
+                data.push(try!(reader.read_u8(8)));
+            }
+            Some(data)
+        } else { None }, write: match *transport_private_data {
+            Some(ref data) => Some(try!(length_prefixed(data))),
+            None => None,
+        } },
+        extension: { value: if flags & 0x01 != 0 {
+            Some(try!(Deserialize::deserialize(reader)))
+        } else { None } },
+        // Stuffing bytes
+        skip: { try!(field_bits_remaining(adaptation_field_length, reader)) }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug,Clone,Copy)]
+    pub struct LegalTimeWindow {
+        pub ltw_valid: bool,
+        pub ltw_offset: u16
+    }
+    deserialize(reader) {
+        ltw_valid: { 1, map: bool_flag },
+        ltw_offset: { 15 }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug,Clone,Copy)]
+    pub struct SeamlessSplice {
+        pub splice_type: u8,
+        pub dts_next_au: u64
+    }
+    deserialize(reader) {
+        splice_type: { 4 },
+        dts_next_au_32_30: { 3, type: u64, write: dts_next_au >> 30 },
+        expect: { bits: 1, reference: 1 }, // marker_bit
+        dts_next_au_29_15: { 15, type: u64, write: dts_next_au >> 15 & 0x7fff },
+        expect: { bits: 1, reference: 1 }, // marker_bit
+        dts_next_au_14_0: { 15, type: u64, write: dts_next_au & 0x7fff },
+        expect: { bits: 1, reference: 1 }, // marker_bit
+        dts_next_au: { value: dts_next_au_32_30 << 30 | dts_next_au_29_15 << 15 | dts_next_au_14_0, write: () }
+    }
+);
+
+bit_struct!(
+    #[derive(Debug,Clone,Copy)]
+    pub struct AdaptationFieldExtension {
+        pub ltw: Option<LegalTimeWindow>,
+        pub piecewise_rate: Option<u32>,
+        pub seamless_splice: Option<SeamlessSplice>
+    }
+    deserialize(reader) {
+        adaptation_field_extension_length: { 8, type: u8, length_of: rest },
+        ltw_flag: { 1, map: bool_flag, write: ltw.is_some() },
+        piecewise_rate_flag: { 1, map: bool_flag, write: piecewise_rate.is_some() },
+        seamless_splice_flag: { 1, map: bool_flag, write: seamless_splice.is_some() },
+        reserved: { 5 },
+        ltw: { value: if ltw_flag {
+            Some(try!(Deserialize::deserialize(reader)))
+        } else { None } },
+        piecewise_rate: { value: if piecewise_rate_flag {
+            try!(reserved(reader, 2));
+            Some(try!(reader.read_u32(22)))
+        } else { None }, write: piecewise_rate.map(|rate| {
+            // Two reserved bits and 22 bits of rate
+            vec![0xc0 | (rate >> 16) as u8 & 0x3f, (rate >> 8) as u8, rate as u8]
+        }) },
+        seamless_splice: { value: if seamless_splice_flag {
+            Some(try!(Deserialize::deserialize(reader)))
+        } else { None } },
+        skip: { try!(field_bits_remaining(adaptation_field_extension_length, reader)) }
+    }
+);
+
+// The adaptation_field_control of a packet, which has to agree with its contents so that the
+// packet is read back as it was written
+fn checked_adaptation_field_control(adaptation_field_control: u8, adaptation_field: &Option<AdaptationField>, payload: &[u8]) -> SerializationResult<u8> {
+    if (adaptation_field_control & 0b10 != 0) != adaptation_field.is_some() {
+        return Err(SerializationError::InvalidValue("adaptation_field_control does not match adaptation_field"));
+    }
+    if adaptation_field_control & 0b01 == 0 && !payload.is_empty() {
+        return Err(SerializationError::InvalidValue("adaptation_field_control does not allow a payload"));
+    }
+    Ok(adaptation_field_control)
+}
+
+// Serialized adaptation field with enough stuffing bytes to fill the packet
+fn stuffed_adaptation_field(adaptation_field: &Option<AdaptationField>, payload_length: usize) -> SerializationResult<Option<Vec<u8>>> {
+    let space = PACKET_SIZE - 4;
+    if payload_length > space {
+        return Err(SerializationError::InvalidValue("payload is longer than 184 bytes"));
+    }
+    let adaptation_field = match *adaptation_field {
+        Some(ref adaptation_field) => adaptation_field,
+        None if payload_length == space => return Ok(None),
+        None => return Err(SerializationError::InvalidValue("payload does not fill the packet and there is no adaptation field")),
+    };
+    let mut bytes = try!(adaptation_field.to_bytes());
+    let available = space - payload_length;
+    if available == 1 && bytes == [1, 0] {
+        // Single byte of stuffing
+        return Ok(Some(vec![0]));
+    }
+    if bytes.len() > available {
+        return Err(SerializationError::InvalidValue("adaptation field and payload do not fit in the packet"));
+    }
+    let stuffing = available - bytes.len();
+    bytes[0] += stuffing as u8;
+    bytes.extend(::std::iter::repeat(0xff).take(stuffing));
+    Ok(Some(bytes))
+}
+
+fn flag_bits(flags: &[bool]) -> u8 {
+    flags.iter().fold(0, |bits, flag| bits << 1 | *flag as u8)
+}
+
+// Remaining bits in a structure that starts with an 8-bit length of the data following it
+fn field_bits_remaining(length: u8, reader: &BitReader) -> DeserializationResult<u64> {
+    let total_bits = (length as u64 + 1) * 8;
+    if reader.position() > total_bits {
+        return Err(DeserializationError::ReadTooMuch {
+            position: reader.position(),
+            max_position: total_bits,
+        });
+    }
+    Ok(total_bits - reader.position())
+}
+
+fn bits_remaining(reader: &BitReader) -> DeserializationResult<u64> {
+    let packet_bits = PACKET_SIZE as u64 * 8;
+    if reader.position() > packet_bits {
+        return Err(DeserializationError::ReadTooMuch {
+            position: reader.position(),
+            max_position: packet_bits,
+        });
+    }
+    Ok(packet_bits - reader.position())
+}
+
+fn read_payload(reader: &mut BitReader) -> DeserializationResult<Vec<u8>> {
+    let bytes = try!(bits_remaining(reader)) / 8;
+    let mut payload = Vec::with_capacity(bytes as usize);
+    for _ in 0..bytes {
+        payload.push(try!(reader.read_u8(8)));
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::base::*;
+    use super::*;
+
+    // PID 0x100 with payload_unit_start_indicator, an adaptation field carrying a PCR and a
+    // payload filling the rest of the packet
+    fn packet_bytes() -> Vec<u8> {
+        let mut bytes = vec![0x47, 0x41, 0x00, 0x35, 0x07, 0x10, 0x09, 0x1a, 0x2b, 0x3c, 0x7f, 0x23];
+        bytes.extend((0..PACKET_SIZE - 12).map(|i| i as u8));
+        bytes
+    }
+
+    #[test]
+    fn null_packet() {
+        let mut bytes = vec![0x47, 0x1f, 0xff, 0x1a];
+        bytes.extend(vec![0xff; PACKET_SIZE - 4]);
+        let packet = TsPacket::from_bytes(&bytes).unwrap();
+        assert_eq!(packet.pid, NULL_PID);
+        assert!(!packet.transport_error_indicator);
+        assert!(!packet.payload_unit_start_indicator);
+        assert!(!packet.has_adaptation_field());
+        assert!(packet.adaptation_field.is_none());
+        assert_eq!(packet.continuity_counter, 10);
+        assert_eq!(packet.payload, vec![0xff; PACKET_SIZE - 4]);
+        assert_eq!(packet.to_bytes().unwrap(), bytes);
+
+        bytes[0] = 0x48;
+        assert!(TsPacket::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn packet_round_trip() {
+        let bytes = packet_bytes();
+        let packet = TsPacket::from_bytes(&bytes).unwrap();
+        assert_eq!(packet.pid, 0x100);
+        assert!(packet.payload_unit_start_indicator);
+        assert_eq!(packet.continuity_counter, 5);
+        assert!(packet.has_adaptation_field());
+        assert!(packet.has_payload());
+        let pcr = packet.adaptation_field.as_ref().unwrap().pcr.unwrap();
+        assert_eq!(pcr.base, 0x12345678);
+        assert_eq!(pcr.extension, 0x123);
+        assert_eq!(packet.payload.len(), PACKET_SIZE - 12);
+        assert_eq!(packet.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn stuffing_round_trip() {
+        let mut packet = TsPacket::from_bytes(&packet_bytes()).unwrap();
+        packet.payload.truncate(100);
+        let bytes = packet.to_bytes().unwrap();
+        assert_eq!(bytes.len(), PACKET_SIZE);
+        assert_eq!(bytes[4] as usize, PACKET_SIZE - 5 - 100);
+        let reparsed = TsPacket::from_bytes(&bytes).unwrap();
+        assert_eq!(reparsed.payload, packet.payload);
+        assert_eq!(reparsed.adaptation_field.unwrap().pcr.unwrap().base, 0x12345678);
+    }
+
+    #[test]
+    fn continuity() {
+        let mut packet = TsPacket::from_bytes(&packet_bytes()).unwrap();
+        let mut tracker = ContinuityTracker::new();
+        let mut push = |continuity_counter: u8, adaptation_field_control: u8, transport_error_indicator: bool| {
+            packet.continuity_counter = continuity_counter;
+            packet.adaptation_field_control = adaptation_field_control;
+            packet.transport_error_indicator = transport_error_indicator;
+            tracker.push(&packet)
+        };
+        assert_eq!(push(14, 0b11, false), Continuity::Continuous);
+        assert_eq!(push(15, 0b11, false), Continuity::Continuous);
+        assert_eq!(push(15, 0b11, false), Continuity::NoPayload);
+        assert_eq!(push(15, 0b10, false), Continuity::NoPayload);
+        assert_eq!(push(0, 0b11, false), Continuity::Continuous);
+        assert_eq!(push(2, 0b11, false), Continuity::Discontinuous);
+        assert_eq!(push(3, 0b11, true), Continuity::TransportError);
+        assert_eq!(push(9, 0b11, false), Continuity::Continuous);
+    }
+
+    #[test]
+    fn duplicate_packets() {
+        let mut packet = TsPacket::from_bytes(&packet_bytes()).unwrap();
+        let mut tracker = ContinuityTracker::new();
+        let mut push = |continuity_counter: u8| {
+            packet.continuity_counter = continuity_counter;
+            tracker.push(&packet)
+        };
+        assert_eq!(push(3), Continuity::Continuous);
+        assert_eq!(push(3), Continuity::NoPayload);
+        assert_eq!(push(4), Continuity::Continuous);
+        assert_eq!(push(4), Continuity::NoPayload);
+        // Only one duplicate is allowed
+        assert_eq!(push(4), Continuity::Discontinuous);
+        assert_eq!(push(5), Continuity::Continuous);
+    }
+
+    #[test]
+    fn discontinuity_indicator() {
+        let mut packet = TsPacket::from_bytes(&packet_bytes()).unwrap();
+        let mut tracker = ContinuityTracker::new();
+        assert_eq!(tracker.push(&packet), Continuity::Continuous);
+        packet.continuity_counter = 9;
+        packet.adaptation_field.as_mut().unwrap().discontinuity_indicator = true;
+        assert_eq!(tracker.push(&packet), Continuity::Continuous);
+        packet.continuity_counter = 10;
+        packet.adaptation_field.as_mut().unwrap().discontinuity_indicator = false;
+        assert_eq!(tracker.push(&packet), Continuity::Continuous);
+        packet.continuity_counter = 12;
+        assert_eq!(tracker.push(&packet), Continuity::Discontinuous);
+    }
+
+    #[test]
+    fn adaptation_field_control_mismatch() {
+        let mut packet = TsPacket::from_bytes(&packet_bytes()).unwrap();
+        packet.adaptation_field_control = 0b01;
+        assert!(packet.to_bytes().is_err());
+        packet.adaptation_field_control = 0b10;
+        assert!(packet.to_bytes().is_err());
+    }
+
+    #[test]
+    fn adaptation_field() {
+        // PCR, OPCR with base 1, splice_countdown -2 and two bytes of private data
+        let mut bytes = vec![0x47, 0x01, 0x00, 0x30, 0x11, 0x9e, 0x09, 0x1a, 0x2b, 0x3c, 0x7f, 0x23,
+            0x00, 0x00, 0x00, 0x00, 0xfe, 0x00, 0xfe, 0x02, 0xaa, 0xbb];
+        bytes.extend(vec![0x55; PACKET_SIZE - 22]);
+        let packet = TsPacket::from_bytes(&bytes).unwrap();
+        let adaptation_field = packet.adaptation_field.as_ref().unwrap();
+        assert!(adaptation_field.discontinuity_indicator);
+        assert!(!adaptation_field.random_access_indicator);
+        assert_eq!(adaptation_field.pcr.unwrap().ticks(), 0x12345678 * 300 + 0x123);
+        assert_eq!(adaptation_field.opcr.unwrap().base, 1);
+        assert_eq!(adaptation_field.opcr.unwrap().extension, 0);
+        assert_eq!(adaptation_field.splice_countdown, Some(-2));
+        assert_eq!(adaptation_field.transport_private_data, Some(vec![0xaa, 0xbb]));
+        assert!(adaptation_field.extension.is_none());
+        assert_eq!(packet.payload.len(), PACKET_SIZE - 22);
+        assert_eq!(packet.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn adaptation_field_only() {
+        let mut bytes = vec![0x47, 0x01, 0x00, 0x20, 0xb7, 0x00];
+        bytes.extend(vec![0xff; PACKET_SIZE - 6]);
+        let packet = TsPacket::from_bytes(&bytes).unwrap();
+        assert!(!packet.has_payload());
+        assert!(packet.payload.is_empty());
+        let adaptation_field = packet.adaptation_field.as_ref().unwrap();
+        assert!(adaptation_field.pcr.is_none());
+        assert!(adaptation_field.splice_countdown.is_none());
+        assert_eq!(packet.to_bytes().unwrap(), bytes);
+    }
+}